@@ -1,40 +1,167 @@
-#[derive(Clone, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AirLookupConfig {
+    // Main trace columns, B tables may be preprocessed instead
     pub a_columns_ids: Vec<usize>,
     pub b_columns_ids: Vec<Vec<usize>>,
     pub a_filter_id: usize,
     pub b_filter_id: Vec<usize>,
+    /// Per B table, whether its columns and filter are preprocessed. Empty if none are.
+    #[serde(default)]
+    pub b_preprocessed: Vec<bool>,
+
+    /// B rows are counted once each instead of by the occurrences columns, so the enabled
+    /// A and B rows must be a permutation of each other.
+    #[serde(default)]
+    pub unit_multiplicity: bool,
+    /// Constant prepended to the A and B tuples, so that arguments sharing a running sum
+    /// can't match each other's rows. Zero if the running sum is not shared.
+    #[serde(default)]
+    pub tag: usize,
+
+    // Auxiliary trace columns (depend on challenges)
     pub a_inverses_id: usize,
     pub b_inverses_id: Vec<usize>,
+    /// Empty with `unit_multiplicity`.
     pub occurrences_id: Vec<usize>,
     pub check_id: usize,
+    /// The running sum `check_id` belongs to an earlier lookup and accumulates the terms of
+    /// this one as well, see `tag`.
+    #[serde(default)]
+    pub shared_check: bool,
+
+    // Names of the main trace columns, in the shape of their ids
+    #[serde(default)]
+    pub a_columns_names: Vec<String>,
+    #[serde(default)]
+    pub b_columns_names: Vec<Vec<String>>,
+    #[serde(default)]
+    pub a_filter_name: String,
+    #[serde(default)]
+    pub b_filter_names: Vec<String>,
 }
 
 impl AirLookupConfig {
-    pub fn shift(&mut self, shift: usize) {
-        self.a_columns_ids
-            .iter_mut()
-            .for_each(|i_column| *i_column = *i_column + shift);
-        self.b_columns_ids.iter_mut().for_each(|i_table| {
-            i_table
-                .iter_mut()
-                .for_each(|i_column| *i_column = *i_column + shift)
-        });
-        self.a_filter_id += shift;
-        self.b_filter_id
-            .iter_mut()
-            .for_each(|i_column| *i_column = *i_column + shift);
-        self.a_inverses_id += shift;
+    /// Whether the columns and the filter of `B[b_table]` are preprocessed columns.
+    pub fn is_b_preprocessed(&self, b_table: usize) -> bool {
+        self.b_preprocessed.get(b_table).copied().unwrap_or(false)
+    }
+
+    pub fn shift(&mut self, main_shift: usize, aux_shift: usize) {
+        self.map_main_ids(|i_column| i_column + main_shift);
+        self.a_inverses_id += aux_shift;
         self.b_inverses_id
             .iter_mut()
-            .for_each(|i_column| *i_column = *i_column + shift);
+            .for_each(|i_column| *i_column = *i_column + aux_shift);
         self.occurrences_id
             .iter_mut()
-            .for_each(|i_column| *i_column = *i_column + shift);
-        self.check_id += shift;
+            .for_each(|i_column| *i_column = *i_column + aux_shift);
+        self.check_id += aux_shift;
+    }
+
+    /// All main trace columns of the argument, in the order of the raw layout: A columns,
+    /// B columns of the main trace tables, A filter and their B filters.
+    pub fn main_columns_ids(&self) -> Vec<usize> {
+        let mut ids = self.a_columns_ids.clone();
+        self.b_columns_ids
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.is_b_preprocessed(*i))
+            .for_each(|(_, i_table)| ids.extend(i_table));
+        ids.push(self.a_filter_id);
+        ids.extend(self.main_b_filters(&self.b_filter_id));
+        ids
+    }
+
+    pub fn main_columns_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.a_columns_names.iter().map(String::as_str).collect();
+        self.b_columns_names
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.is_b_preprocessed(*i))
+            .for_each(|(_, i_table)| names.extend(i_table.iter().map(String::as_str)));
+        names.push(&self.a_filter_name);
+        names.extend(
+            self.main_b_filters(&self.b_filter_names)
+                .map(String::as_str),
+        );
+        names
+    }
+
+    /// All preprocessed columns of the argument: B columns of the preprocessed tables,
+    /// then their B filters.
+    pub fn preprocessed_columns_ids(&self) -> Vec<usize> {
+        let mut ids = Vec::new();
+        self.b_columns_ids
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.is_b_preprocessed(*i))
+            .for_each(|(_, i_table)| ids.extend(i_table));
+        ids.extend(
+            self.b_filter_id
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| self.is_b_preprocessed(*i))
+                .map(|(_, i_column)| i_column),
+        );
+        ids
+    }
+
+    pub fn map_main_ids<F: Fn(usize) -> usize>(&mut self, f: F) {
+        self.a_columns_ids
+            .iter_mut()
+            .for_each(|i_column| *i_column = f(*i_column));
+        self.a_filter_id = f(self.a_filter_id);
+        self.map_b_ids(false, f);
+    }
+
+    pub fn map_preprocessed_ids<F: Fn(usize) -> usize>(&mut self, f: F) {
+        self.map_b_ids(true, f);
+    }
+
+    fn map_b_ids<F: Fn(usize) -> usize>(&mut self, preprocessed: bool, f: F) {
+        let b_preprocessed: Vec<bool> = (0..self.b_columns_ids.len())
+            .map(|i| self.is_b_preprocessed(i))
+            .collect();
+        for ((i_table, i_filter), is_preprocessed) in self
+            .b_columns_ids
+            .iter_mut()
+            .zip(self.b_filter_id.iter_mut())
+            .zip(b_preprocessed)
+        {
+            if is_preprocessed == preprocessed {
+                i_table
+                    .iter_mut()
+                    .for_each(|i_column| *i_column = f(*i_column));
+                *i_filter = f(*i_filter);
+            }
+        }
+    }
+
+    /// Values of the B filters of the main trace tables.
+    fn main_b_filters<'a, T>(&'a self, values: &'a [T]) -> impl Iterator<Item = &'a T> + 'a {
+        values
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.is_b_preprocessed(*i))
+            .map(|(_, value)| value)
+    }
+
+    pub fn main_width(&self) -> usize {
+        self.main_columns_ids().len()
+    }
+
+    pub fn aux_columns_ids(&self) -> Vec<usize> {
+        let mut ids = vec![self.a_inverses_id];
+        ids.extend(&self.b_inverses_id);
+        ids.extend(&self.occurrences_id);
+        ids.push(self.check_id);
+        ids
     }
 
-    pub fn width(&self) -> usize {
-        self.a_columns_ids.len() + self.b_columns_ids.len() * (self.b_columns_ids[0].len() + 3) + 3
+    pub fn aux_width(&self) -> usize {
+        // A shared running sum is counted by the lookup owning it
+        1 + self.b_inverses_id.len() + self.occurrences_id.len() + !self.shared_check as usize
     }
 }
@@ -1,24 +1,88 @@
-#[derive(Clone, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AirPermutationConfig {
+    // Main trace columns
     pub a_columns_ids: Vec<usize>,
     pub b_columns_ids: Vec<usize>,
+    /// Optional filters, rows disabled by them contribute a neutral factor.
+    #[serde(default)]
+    pub a_filter_id: Option<usize>,
+    #[serde(default)]
+    pub b_filter_id: Option<usize>,
+
+    // Auxiliary trace columns (depend on challenges)
     pub b_inverse_id: usize,
     pub check_id: usize,
+
+    // Names of the main trace columns, in the shape of their ids
+    #[serde(default)]
+    pub a_columns_names: Vec<String>,
+    #[serde(default)]
+    pub b_columns_names: Vec<String>,
+    #[serde(default)]
+    pub a_filter_name: String,
+    #[serde(default)]
+    pub b_filter_name: String,
 }
 
 impl AirPermutationConfig {
-    pub fn shift(&mut self, shift: usize) {
+    pub fn shift(&mut self, main_shift: usize, aux_shift: usize) {
+        self.map_main_ids(|i_column| i_column + main_shift);
+        self.b_inverse_id += aux_shift;
+        self.check_id += aux_shift;
+    }
+
+    /// All main trace columns of the argument, in the order of the raw layout: A columns,
+    /// B columns, then the filters that are present.
+    pub fn main_columns_ids(&self) -> Vec<usize> {
+        let mut ids = self.a_columns_ids.clone();
+        ids.extend(&self.b_columns_ids);
+        ids.extend(self.a_filter_id);
+        ids.extend(self.b_filter_id);
+        ids
+    }
+
+    pub fn main_columns_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .a_columns_names
+            .iter()
+            .chain(&self.b_columns_names)
+            .map(String::as_str)
+            .collect();
+        if self.a_filter_id.is_some() {
+            names.push(&self.a_filter_name);
+        }
+        if self.b_filter_id.is_some() {
+            names.push(&self.b_filter_name);
+        }
+        names
+    }
+
+    pub fn preprocessed_columns_ids(&self) -> Vec<usize> {
+        vec![]
+    }
+
+    pub fn map_main_ids<F: Fn(usize) -> usize>(&mut self, f: F) {
         self.a_columns_ids
             .iter_mut()
-            .for_each(|i_column| *i_column = *i_column + shift);
-        self.b_columns_ids
-            .iter_mut()
-            .for_each(|i_column| *i_column = *i_column + shift);
-        self.b_inverse_id += shift;
-        self.check_id += shift;
+            .chain(self.b_columns_ids.iter_mut())
+            .chain(self.a_filter_id.iter_mut())
+            .chain(self.b_filter_id.iter_mut())
+            .for_each(|i_column| *i_column = f(*i_column));
+    }
+
+    pub fn map_preprocessed_ids<F: Fn(usize) -> usize>(&mut self, _f: F) {}
+
+    pub fn main_width(&self) -> usize {
+        self.main_columns_ids().len()
+    }
+
+    pub fn aux_columns_ids(&self) -> Vec<usize> {
+        vec![self.b_inverse_id, self.check_id]
     }
 
-    pub fn width(&self) -> usize {
-        self.a_columns_ids.len() + self.b_columns_ids.len() + 2
+    pub fn aux_width(&self) -> usize {
+        2
     }
 }
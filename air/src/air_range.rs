@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// Widest limb of a range check, wider values are decomposed into limbs of this width and a
+/// narrower last limb, so that no range table takes more than `2^RANGE_LIMB_BITS` rows.
+pub const RANGE_LIMB_BITS: usize = 16;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AirRangeConfig {
+    // Main trace columns
+    pub column_id: usize,
+    pub filter_id: usize,
+    /// Little-endian limbs of the value, each checked in its own range table. Empty if the
+    /// value is narrow enough to be checked as a single limb.
+    pub limbs_id: Vec<usize>,
+
+    // Preprocessed columns
+    /// Range table per limb, see `FixedColumn::Range`.
+    pub table_id: Vec<usize>,
+    pub bits: usize,
+    /// Width per limb, every limb but the last one is `RANGE_LIMB_BITS` wide.
+    pub limb_bits: Vec<usize>,
+
+    // Auxiliary trace columns (depend on challenges), per limb except for the running sum
+    pub inverses_id: Vec<usize>,
+    pub table_inverses_id: Vec<usize>,
+    pub occurrences_id: Vec<usize>,
+    pub check_id: usize,
+
+    // Names of the main trace columns, in the shape of their ids
+    #[serde(default)]
+    pub column_name: String,
+    #[serde(default)]
+    pub filter_name: String,
+    #[serde(default)]
+    pub limbs_names: Vec<String>,
+    #[serde(default)]
+    pub table_names: Vec<String>,
+}
+
+impl AirRangeConfig {
+    /// Main trace columns looked up into the range tables: the limbs, or the value itself.
+    pub fn checked_columns_ids(&self) -> Vec<usize> {
+        if self.limbs_id.is_empty() {
+            vec![self.column_id]
+        } else {
+            self.limbs_id.clone()
+        }
+    }
+
+    pub fn shift(&mut self, main_shift: usize, aux_shift: usize) {
+        self.map_main_ids(|i_column| i_column + main_shift);
+        for ids in [
+            &mut self.inverses_id,
+            &mut self.table_inverses_id,
+            &mut self.occurrences_id,
+        ] {
+            ids.iter_mut()
+                .for_each(|i_column| *i_column = *i_column + aux_shift);
+        }
+        self.check_id += aux_shift;
+    }
+
+    pub fn main_columns_ids(&self) -> Vec<usize> {
+        let mut ids = vec![self.column_id, self.filter_id];
+        ids.extend(&self.limbs_id);
+        ids
+    }
+
+    pub fn main_columns_names(&self) -> Vec<&str> {
+        let mut names = vec![self.column_name.as_str(), self.filter_name.as_str()];
+        names.extend(self.limbs_names.iter().map(String::as_str));
+        names
+    }
+
+    pub fn preprocessed_columns_ids(&self) -> Vec<usize> {
+        self.table_id.clone()
+    }
+
+    pub fn map_main_ids<F: Fn(usize) -> usize>(&mut self, f: F) {
+        self.column_id = f(self.column_id);
+        self.filter_id = f(self.filter_id);
+        self.limbs_id
+            .iter_mut()
+            .for_each(|i_column| *i_column = f(*i_column));
+    }
+
+    pub fn map_preprocessed_ids<F: Fn(usize) -> usize>(&mut self, f: F) {
+        self.table_id
+            .iter_mut()
+            .for_each(|i_column| *i_column = f(*i_column));
+    }
+
+    pub fn main_width(&self) -> usize {
+        2 + self.limbs_id.len()
+    }
+
+    pub fn aux_columns_ids(&self) -> Vec<usize> {
+        let mut ids = Vec::new();
+        ids.extend(&self.inverses_id);
+        ids.extend(&self.table_inverses_id);
+        ids.extend(&self.occurrences_id);
+        ids.push(self.check_id);
+        ids
+    }
+
+    pub fn aux_width(&self) -> usize {
+        3 * self.table_id.len() + 1
+    }
+}
@@ -1,39 +1,210 @@
 pub mod air_lookup;
 pub mod air_permutation;
+pub mod air_range;
+pub mod preprocessed;
 
 use crate::air_permutation::AirPermutationConfig;
+use crate::air_range::AirRangeConfig;
 use air_lookup::AirLookupConfig;
-use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, BaseAir};
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, BaseAir, PairBuilder};
 use p3_field::{Field, FieldAlgebra};
+use p3_matrix::dense::RowMajorMatrix;
 use p3_matrix::Matrix;
-use std::ops::{Add, Mul, Sub};
+use preprocessed::FixedColumn;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Amount of challenges (`alpha` and `delta`) sampled after committing the main trace.
+pub const NUM_CHALLENGES: usize = 2;
+
+/// Builder for AIRs proved in two stages: the main trace is committed first, then the
+/// challenges are sampled from the transcript and the auxiliary trace is committed.
+/// Preprocessed columns are committed once in the verifying key, before both stages.
+pub trait MultiStageAirBuilder: AirBuilderWithPublicValues + PairBuilder {
+    /// Auxiliary trace, generated from the main trace and the challenges.
+    fn aux(&self) -> Self::M;
+
+    /// Challenges sampled after committing the main trace.
+    fn challenges(&self) -> &[Self::PublicVar];
+
+    /// Marks the constraints asserted next as the `kind` constraints of the `argument`-th
+    /// config, so that debugging builders can report them. Ignored by default.
+    fn annotate(&mut self, _argument: usize, _kind: ConstraintKind) {}
+}
+
+/// Kind of an argument constraint, see [`MultiStageAirBuilder::annotate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintKind {
+    /// Inverse column times the shifted combination is one.
+    Inverse,
+    /// Check column starts from the first row term.
+    FirstRow,
+    /// Check column accumulates the next row term.
+    Transition,
+    /// Check column ends with the neutral value.
+    LastRow,
+    /// Filter is either zero or one.
+    Filter,
+    /// Limbs of an enabled range checked value add up to it.
+    Decomposition,
+}
+
+impl Display for ConstraintKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstraintKind::Inverse => write!(f, "inverse"),
+            ConstraintKind::FirstRow => write!(f, "first row"),
+            ConstraintKind::Transition => write!(f, "transition"),
+            ConstraintKind::LastRow => write!(f, "last row"),
+            ConstraintKind::Filter => write!(f, "boolean filter"),
+            ConstraintKind::Decomposition => write!(f, "limb decomposition"),
+        }
+    }
+}
+
+pub trait MultiStageAir<F>: BaseAir<F> {
+    fn aux_width(&self) -> usize;
 
-#[derive(Clone, Debug)]
+    /// Width of `preprocessed_trace`, zero if there is none.
+    fn preprocessed_width(&self) -> usize {
+        0
+    }
+
+    fn num_challenges(&self) -> usize;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum AirConfig {
     Lookup(AirLookupConfig),
     Permutation(AirPermutationConfig),
+    Range(AirRangeConfig),
 }
 
 impl AirConfig {
-    pub fn width(&self) -> usize {
+    /// All main trace columns of the argument, in the order of its raw layout.
+    pub fn main_columns_ids(&self) -> Vec<usize> {
+        match self {
+            AirConfig::Lookup(l) => l.main_columns_ids(),
+            AirConfig::Permutation(p) => p.main_columns_ids(),
+            AirConfig::Range(r) => r.main_columns_ids(),
+        }
+    }
+
+    /// Names of the main trace columns, in the `main_columns_ids` order.
+    pub fn main_columns_names(&self) -> Vec<&str> {
+        match self {
+            AirConfig::Lookup(l) => l.main_columns_names(),
+            AirConfig::Permutation(p) => p.main_columns_names(),
+            AirConfig::Range(r) => r.main_columns_names(),
+        }
+    }
+
+    /// Name of the main trace column `id`, if the argument references it.
+    pub fn main_column_name(&self, id: usize) -> Option<&str> {
+        let position = self.main_columns_ids().iter().position(|i| *i == id)?;
+        self.main_columns_names().get(position).copied()
+    }
+
+    /// Moves main trace columns to `f(id)`, e.g. to point at columns shared with other
+    /// arguments.
+    pub fn map_main_ids<F: Fn(usize) -> usize>(&mut self, f: F) {
+        match self {
+            AirConfig::Lookup(l) => l.map_main_ids(f),
+            AirConfig::Permutation(p) => p.map_main_ids(f),
+            AirConfig::Range(r) => r.map_main_ids(f),
+        }
+    }
+
+    /// All preprocessed columns of the argument, none for permutations.
+    pub fn preprocessed_columns_ids(&self) -> Vec<usize> {
+        match self {
+            AirConfig::Lookup(l) => l.preprocessed_columns_ids(),
+            AirConfig::Permutation(p) => p.preprocessed_columns_ids(),
+            AirConfig::Range(r) => r.preprocessed_columns_ids(),
+        }
+    }
+
+    /// Moves preprocessed columns to `f(id)`.
+    pub fn map_preprocessed_ids<F: Fn(usize) -> usize>(&mut self, f: F) {
+        match self {
+            AirConfig::Lookup(l) => l.map_preprocessed_ids(f),
+            AirConfig::Permutation(p) => p.map_preprocessed_ids(f),
+            AirConfig::Range(r) => r.map_preprocessed_ids(f),
+        }
+    }
+
+    pub fn shift(&mut self, main_shift: usize, aux_shift: usize) {
+        match self {
+            AirConfig::Lookup(l) => l.shift(main_shift, aux_shift),
+            AirConfig::Permutation(p) => p.shift(main_shift, aux_shift),
+            AirConfig::Range(r) => r.shift(main_shift, aux_shift),
+        }
+    }
+
+    /// Amount of main trace columns referenced by the argument, shared ones included.
+    pub fn main_width(&self) -> usize {
+        match self {
+            AirConfig::Lookup(l) => l.main_width(),
+            AirConfig::Permutation(p) => p.main_width(),
+            AirConfig::Range(r) => r.main_width(),
+        }
+    }
+
+    /// Auxiliary trace columns of the argument, in the order they are generated.
+    pub fn aux_columns_ids(&self) -> Vec<usize> {
         match self {
-            AirConfig::Lookup(l) => l.width(),
-            AirConfig::Permutation(p) => p.width(),
+            AirConfig::Lookup(l) => l.aux_columns_ids(),
+            AirConfig::Permutation(p) => p.aux_columns_ids(),
+            AirConfig::Range(r) => r.aux_columns_ids(),
+        }
+    }
+
+    /// Amount of auxiliary trace columns owned by the argument.
+    pub fn aux_width(&self) -> usize {
+        match self {
+            AirConfig::Lookup(l) => l.aux_width(),
+            AirConfig::Permutation(p) => p.aux_width(),
+            AirConfig::Range(r) => r.aux_width(),
         }
     }
 }
 
+/// Width of the main trace used by `configs`. Arguments may share columns, so it can be
+/// less than the sum of their widths.
+pub fn main_trace_width(configs: &[AirConfig]) -> usize {
+    configs
+        .iter()
+        .flat_map(AirConfig::main_columns_ids)
+        .max()
+        .map_or(0, |id| id + 1)
+}
+
 #[derive(Clone)]
 pub struct LineaAIR {
     configs: Vec<AirConfig>,
     width: usize,
+    aux_width: usize,
+    preprocessed: Vec<FixedColumn>,
+    height: usize,
 }
 
 impl LineaAIR {
     pub fn new(configs: Vec<AirConfig>) -> Self {
+        Self::with_preprocessed(configs, vec![], 0)
+    }
+
+    /// AIR whose configs reference the `preprocessed` columns, padded to `height` rows.
+    pub fn with_preprocessed(
+        configs: Vec<AirConfig>,
+        preprocessed: Vec<FixedColumn>,
+        height: usize,
+    ) -> Self {
         Self {
-            width: configs.iter().map(|c| c.width()).sum(),
+            width: main_trace_width(&configs),
+            aux_width: configs.iter().map(|c| c.aux_width()).sum(),
             configs,
+            preprocessed,
+            height,
         }
     }
 }
@@ -42,127 +213,327 @@ impl<F: Field> BaseAir<F> for LineaAIR {
     fn width(&self) -> usize {
         self.width
     }
+
+    fn preprocessed_trace(&self) -> Option<RowMajorMatrix<F>> {
+        if self.preprocessed.is_empty() {
+            return None;
+        }
+
+        Some(RowMajorMatrix::new(
+            preprocessed::row_major_values(&self.preprocessed, self.height),
+            self.preprocessed.len(),
+        ))
+    }
 }
 
-impl<AB: AirBuilderWithPublicValues> Air<AB> for LineaAIR {
+impl<F: Field> MultiStageAir<F> for LineaAIR {
+    fn aux_width(&self) -> usize {
+        self.aux_width
+    }
+
+    fn preprocessed_width(&self) -> usize {
+        self.preprocessed.len()
+    }
+
+    fn num_challenges(&self) -> usize {
+        NUM_CHALLENGES
+    }
+}
+
+impl<AB: MultiStageAirBuilder> Air<AB> for LineaAIR {
     fn eval(&self, builder: &mut AB) {
-        self.configs.iter().for_each(|c| match c {
-            AirConfig::Lookup(l) => self.eval_lookup(builder, l),
-            AirConfig::Permutation(p) => self.eval_permutation(builder, p),
-        });
+        // Log-derivative terms of the local and the next rows per running sum column, along
+        // with the argument owning the column. Several lookups may share one running sum
+        let mut sums: Vec<(usize, usize, AB::Expr, AB::Expr)> = Vec::new();
+
+        for (i, c) in self.configs.iter().enumerate() {
+            let (check_id, (local_term, next_term)) = match c {
+                AirConfig::Lookup(l) => (l.check_id, self.eval_lookup(builder, i, l)),
+                AirConfig::Permutation(p) => {
+                    self.eval_permutation(builder, i, p);
+                    continue;
+                }
+                AirConfig::Range(r) => (r.check_id, self.eval_range(builder, i, r)),
+            };
+
+            match sums.iter_mut().find(|(_, id, _, _)| *id == check_id) {
+                Some((_, _, local_sum, next_sum)) => {
+                    *local_sum += local_term;
+                    *next_sum += next_term;
+                }
+                None => sums.push((i, check_id, local_term, next_term)),
+            }
+        }
+
+        for (argument, check_id, local_term, next_term) in sums {
+            self.eval_running_sum(builder, argument, check_id, local_term, next_term);
+        }
     }
 }
 
 impl LineaAIR {
-    fn eval_lookup<AB: AirBuilderWithPublicValues>(&self, builder: &mut AB, l: &AirLookupConfig) {
+    /// Constrains the lookup columns, except for the running sum, and returns the
+    /// log-derivative terms of the local and the next rows.
+    fn eval_lookup<AB: MultiStageAirBuilder>(
+        &self,
+        builder: &mut AB,
+        argument: usize,
+        l: &AirLookupConfig,
+    ) -> (AB::Expr, AB::Expr) {
         let main = builder.main();
+        let aux = builder.aux();
+        let fixed = builder.preprocessed();
 
         let local = main.row_slice(0);
         let next = main.row_slice(1);
 
-        let alpha = builder.public_values()[0].into();
-        let delta = builder.public_values()[1].into();
+        let aux_local = aux.row_slice(0);
+        let aux_next = aux.row_slice(1);
 
-        let mut a_local_comb = AB::Expr::from(AB::F::ZERO);
+        let fixed_local = fixed.row_slice(0);
+        let fixed_next = fixed.row_slice(1);
+
+        let alpha = builder.challenges()[0].into();
+        let delta = builder.challenges()[1].into();
+
+        // Tagged tuples are combined as `(tag, a[0], ..., a[n-1])`
+        let tag = AB::Expr::from_canonical_usize(l.tag);
+
+        let mut a_local_comb = tag.clone();
         for i in &l.a_columns_ids {
             a_local_comb = a_local_comb * alpha.clone() + local[*i]
         }
 
         let a_local_challenge = a_local_comb + delta.clone();
 
+        // Check filters are boolean, otherwise they would scale the log-derivative terms.
+        // Preprocessed ones are fixed by the verifying key
+        builder.annotate(argument, ConstraintKind::Filter);
+        builder.assert_bool(local[l.a_filter_id]);
+        for (b_table_ind, b_filter_id) in l.b_filter_id.iter().enumerate() {
+            if !l.is_b_preprocessed(b_table_ind) {
+                builder.assert_bool(local[*b_filter_id]);
+            }
+        }
+
         // Check inverse calculated correctly
-        builder.assert_eq(a_local_challenge * local[l.a_inverses_id], AB::F::ONE);
+        builder.annotate(argument, ConstraintKind::Inverse);
+        builder.assert_eq(a_local_challenge * aux_local[l.a_inverses_id], AB::F::ONE);
 
-        let mut local_check = local[l.a_filter_id] * local[l.a_inverses_id];
-        let mut next_check = next[l.a_filter_id] * next[l.a_inverses_id];
+        let mut local_check = local[l.a_filter_id] * aux_local[l.a_inverses_id];
+        let mut next_check = next[l.a_filter_id] * aux_next[l.a_inverses_id];
 
         for (b_table_ind, b_columns_ids) in l.b_columns_ids.iter().enumerate() {
-            let mut b_local_comb = AB::Expr::from(AB::F::ZERO);
+            let (b_local, b_next) = if l.is_b_preprocessed(b_table_ind) {
+                (&*fixed_local, &*fixed_next)
+            } else {
+                (&*local, &*next)
+            };
+
+            let mut b_local_comb = tag.clone();
             for i in b_columns_ids {
-                b_local_comb = b_local_comb * alpha.clone() + local[*i]
+                b_local_comb = b_local_comb * alpha.clone() + b_local[*i]
             }
 
             let b_local_challenge = b_local_comb + delta.clone();
             builder.assert_eq(
-                b_local_challenge * local[l.b_inverses_id[b_table_ind]],
+                b_local_challenge * aux_local[l.b_inverses_id[b_table_ind]],
                 AB::F::ONE,
             );
 
-            local_check -= local[l.b_filter_id[b_table_ind]]
-                * local[l.occurrences_id[b_table_ind]]
-                * local[l.b_inverses_id[b_table_ind]];
+            let b_filter_id = l.b_filter_id[b_table_ind];
+            let b_inverses_id = l.b_inverses_id[b_table_ind];
+
+            // Enabled B rows are counted once with unit multiplicities
+            if l.unit_multiplicity {
+                local_check -= b_local[b_filter_id] * aux_local[b_inverses_id];
+                next_check -= b_next[b_filter_id] * aux_next[b_inverses_id];
+            } else {
+                let occurrences_id = l.occurrences_id[b_table_ind];
+                local_check -=
+                    b_local[b_filter_id] * aux_local[occurrences_id] * aux_local[b_inverses_id];
+                next_check -=
+                    b_next[b_filter_id] * aux_next[occurrences_id] * aux_next[b_inverses_id];
+            }
+        }
+
+        (local_check, next_check)
+    }
+
+    fn eval_permutation<AB: MultiStageAirBuilder>(
+        &self,
+        builder: &mut AB,
+        argument: usize,
+        p: &AirPermutationConfig,
+    ) {
+        let main = builder.main();
+        let aux = builder.aux();
+
+        let local = main.row_slice(0);
+        let next = main.row_slice(1);
+
+        let aux_local = aux.row_slice(0);
+        let aux_next = aux.row_slice(1);
+
+        let alpha: AB::Expr = builder.challenges()[0].into();
+        let delta: AB::Expr = builder.challenges()[1].into();
+
+        // Shifted combination of a row, or one if the row is disabled by the filter
+        let factor = |row: &[AB::Var], columns_ids: &[usize], filter_id: Option<usize>| {
+            let mut comb = AB::Expr::from(AB::F::ZERO);
+            for i in columns_ids {
+                comb = comb * alpha.clone() + row[*i]
+            }
+            let challenge = comb + delta.clone();
+
+            match filter_id {
+                Some(id) => challenge * row[id] + AB::Expr::ONE - row[id],
+                None => challenge,
+            }
+        };
 
-            next_check -= next[l.b_filter_id[b_table_ind]]
-                * next[l.occurrences_id[b_table_ind]]
-                * next[l.b_inverses_id[b_table_ind]]
+        let a_local_factor = factor(&local, &p.a_columns_ids, p.a_filter_id);
+        let b_local_factor = factor(&local, &p.b_columns_ids, p.b_filter_id);
+        let a_next_factor = factor(&next, &p.a_columns_ids, p.a_filter_id);
+        let b_next_factor = factor(&next, &p.b_columns_ids, p.b_filter_id);
+
+        // Check filters are boolean, otherwise disabled rows wouldn't be neutral
+        builder.annotate(argument, ConstraintKind::Filter);
+        for filter_id in p.a_filter_id.iter().chain(&p.b_filter_id) {
+            builder.assert_bool(local[*filter_id]);
         }
 
-        // Check first row calculated correctly
+        // Check inverse calculated correctly, so the B factors are non-zero
+        builder.annotate(argument, ConstraintKind::Inverse);
+        builder.assert_eq(
+            b_local_factor.clone() * aux_local[p.b_inverse_id],
+            AB::F::ONE,
+        );
+
+        // Check first row calculated correctly. The running product is multiplied by the B
+        // factors instead of their inverses to keep the degree of filtered factors low
+        builder.annotate(argument, ConstraintKind::FirstRow);
         builder
             .when_first_row()
-            .assert_eq(local[l.check_id], local_check);
+            .assert_eq(aux_local[p.check_id] * b_local_factor, a_local_factor);
 
         // Check each row transition
-        builder
-            .when_transition()
-            .assert_eq(next[l.check_id] - local[l.check_id], next_check);
+        builder.annotate(argument, ConstraintKind::Transition);
+        builder.when_transition().assert_eq(
+            aux_next[p.check_id] * b_next_factor,
+            aux_local[p.check_id] * a_next_factor,
+        );
 
-        // Check total sum is zero
+        // Check total prod is one
+        builder.annotate(argument, ConstraintKind::LastRow);
         builder
             .when_last_row()
-            .assert_eq(local[l.check_id], AB::F::ZERO);
+            .assert_eq(aux_local[p.check_id], AB::F::ONE);
     }
 
-    fn eval_permutation<AB: AirBuilderWithPublicValues>(
+    /// Constrains the range check columns, except for the running sum, and returns the
+    /// log-derivative terms of the local and the next rows. Every limb is looked up into its
+    /// own range table and all of them add into the one running sum.
+    fn eval_range<AB: MultiStageAirBuilder>(
         &self,
         builder: &mut AB,
-        p: &AirPermutationConfig,
-    ) {
+        argument: usize,
+        r: &AirRangeConfig,
+    ) -> (AB::Expr, AB::Expr) {
         let main = builder.main();
+        let aux = builder.aux();
+        let fixed = builder.preprocessed();
 
         let local = main.row_slice(0);
         let next = main.row_slice(1);
 
-        let alpha = builder.public_values()[0].into();
-        let delta = builder.public_values()[1].into();
+        let aux_local = aux.row_slice(0);
+        let aux_next = aux.row_slice(1);
 
-        let mut a_local_comb = AB::Expr::from(AB::F::ZERO);
-        for i in &p.a_columns_ids {
-            a_local_comb = a_local_comb * alpha.clone() + local[*i]
+        // Range tables are preprocessed, so they hold every value of the range and nothing else
+        let table_local = fixed.row_slice(0);
+
+        let delta: AB::Expr = builder.challenges()[1].into();
+
+        // Check filter is boolean
+        builder.annotate(argument, ConstraintKind::Filter);
+        builder.assert_bool(local[r.filter_id]);
+
+        // Check limbs add up to the value. The sum of in-range limbs is below the field
+        // modulus, so the decomposition is unique
+        if !r.limbs_id.is_empty() {
+            builder.annotate(argument, ConstraintKind::Decomposition);
+            let mut sum = AB::Expr::ZERO;
+            let mut shift = AB::F::ONE;
+            for (limb_id, limb_bits) in r.limbs_id.iter().zip(&r.limb_bits) {
+                sum += local[*limb_id] * shift;
+                shift *= AB::F::from_canonical_u64(1 << limb_bits);
+            }
+            builder.assert_zero(local[r.filter_id] * (local[r.column_id] - sum));
         }
 
-        let mut b_local_comb = AB::Expr::from(AB::F::ZERO);
-        for i in &p.b_columns_ids {
-            b_local_comb = b_local_comb * alpha.clone() + local[*i]
+        let mut local_check = AB::Expr::ZERO;
+        let mut next_check = AB::Expr::ZERO;
+
+        let checked_ids = r.checked_columns_ids();
+        for (limb, checked_id) in checked_ids.iter().enumerate() {
+            let inverse_id = r.inverses_id[limb];
+            let table_inverse_id = r.table_inverses_id[limb];
+            let occurrences_id = r.occurrences_id[limb];
+
+            // Check inverses calculated correctly, single columns are combined as `value + delta`
+            builder.annotate(argument, ConstraintKind::Inverse);
+            builder.assert_eq(
+                (local[*checked_id] + delta.clone()) * aux_local[inverse_id],
+                AB::F::ONE,
+            );
+            builder.assert_eq(
+                (table_local[r.table_id[limb]] + delta.clone()) * aux_local[table_inverse_id],
+                AB::F::ONE,
+            );
+
+            local_check += local[r.filter_id] * aux_local[inverse_id]
+                - aux_local[occurrences_id] * aux_local[table_inverse_id];
+            next_check += next[r.filter_id] * aux_next[inverse_id]
+                - aux_next[occurrences_id] * aux_next[table_inverse_id];
         }
 
-        let a_local_challenge = a_local_comb + delta.clone();
-        let b_local_challenge = b_local_comb + delta.clone();
+        (local_check, next_check)
+    }
 
-        // Check inverse calculated correctly
-        builder.assert_eq(b_local_challenge * local[p.b_inverse_id], AB::F::ONE);
+    /// Constrains the running sum `check_id` of the log-derivative terms of one or more
+    /// arguments, annotated with the `argument` owning the column. Failures of a shared
+    /// running sum can't be attributed to a single argument, so reports have to list all
+    /// lookups with this `check_id`.
+    fn eval_running_sum<AB: MultiStageAirBuilder>(
+        &self,
+        builder: &mut AB,
+        argument: usize,
+        check_id: usize,
+        local_term: AB::Expr,
+        next_term: AB::Expr,
+    ) {
+        let aux = builder.aux();
+
+        let aux_local = aux.row_slice(0);
+        let aux_next = aux.row_slice(1);
 
         // Check first row calculated correctly
+        builder.annotate(argument, ConstraintKind::FirstRow);
         builder
             .when_first_row()
-            .assert_eq(local[p.check_id], a_local_challenge * local[p.b_inverse_id]);
-
-        let mut a_next_comb = AB::Expr::from(AB::F::ZERO);
-        for i in &p.a_columns_ids {
-            a_next_comb = a_next_comb * alpha.clone() + next[*i]
-        }
-
-        let a_next_challenge = a_next_comb + delta.clone();
+            .assert_eq(aux_local[check_id], local_term);
 
         // Check each row transition
-        builder.when_transition().assert_eq(
-            next[p.check_id],
-            local[p.check_id] * a_next_challenge * next[p.b_inverse_id],
-        );
+        builder.annotate(argument, ConstraintKind::Transition);
+        builder
+            .when_transition()
+            .assert_eq(aux_next[check_id] - aux_local[check_id], next_term);
 
-        // Check total prod is one
+        // Check total sum is zero
+        builder.annotate(argument, ConstraintKind::LastRow);
         builder
             .when_last_row()
-            .assert_eq(local[p.check_id], AB::F::ONE);
+            .assert_eq(aux_local[check_id], AB::F::ZERO);
     }
 }
@@ -0,0 +1,52 @@
+use p3_field::Field;
+use serde::{Deserialize, Serialize};
+
+/// Preprocessed column, committed once at setup, so that the prover can't change it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FixedColumn {
+    /// Range table `2^bits - 1` down to `0`, followed by zeros.
+    Range { bits: usize },
+    /// Explicit big-endian values, followed by zeros.
+    Values(Vec<[u8; 32]>),
+}
+
+impl FixedColumn {
+    /// Amount of rows that are not padding.
+    pub fn len(&self) -> usize {
+        match self {
+            FixedColumn::Range { bits } => 1 << bits,
+            FixedColumn::Values(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Values of the column padded with zeros to `height` rows.
+    pub fn values<F: Field>(&self, height: usize) -> Vec<F> {
+        let mut values: Vec<F> = match self {
+            FixedColumn::Range { bits } => {
+                (0..1u64 << bits).rev().map(F::from_canonical_u64).collect()
+            }
+            FixedColumn::Values(values) => values
+                .iter()
+                .map(|bytes| {
+                    bytes.iter().fold(F::ZERO, |acc, byte| {
+                        acc * F::from_canonical_u16(256) + F::from_canonical_u8(*byte)
+                    })
+                })
+                .collect(),
+        };
+        values.resize(height, F::ZERO);
+        values
+    }
+}
+
+/// Row-major values of the `columns` padded with zeros to `height` rows.
+pub fn row_major_values<F: Field>(columns: &[FixedColumn], height: usize) -> Vec<F> {
+    let columns: Vec<Vec<F>> = columns.iter().map(|c| c.values(height)).collect();
+    (0..height)
+        .flat_map(|i| columns.iter().map(move |column| column[i]))
+        .collect()
+}
@@ -0,0 +1,83 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{Read, Write};
+
+/// Error of reading or writing a prover artifact (proof or verifying key).
+pub enum ArtifactError {
+    Io(String, std::io::Error),
+    Encode(String),
+    Decode(String),
+    VersionMismatch { expected: u32, found: u32 },
+    HashParametersMismatch,
+}
+
+impl Display for ArtifactError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArtifactError::Io(path, e) => write!(f, "{}: {}", path, e),
+            ArtifactError::Encode(e) => write!(f, "failed to encode: {}", e),
+            ArtifactError::Decode(e) => write!(f, "failed to decode: {}", e),
+            ArtifactError::VersionMismatch { expected, found } => write!(
+                f,
+                "unsupported format version {} (expected {})",
+                found, expected
+            ),
+            ArtifactError::HashParametersMismatch => {
+                write!(f, "generated with different Poseidon2 parameters")
+            }
+        }
+    }
+}
+
+impl Debug for ArtifactError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+pub(crate) fn write_cbor<T: Serialize, W: Write>(
+    value: &T,
+    writer: &mut W,
+) -> Result<(), ArtifactError> {
+    ciborium::into_writer(value, writer).map_err(|e| ArtifactError::Encode(format!("{:?}", e)))
+}
+
+pub(crate) fn read_cbor<T: DeserializeOwned, R: Read>(reader: &mut R) -> Result<T, ArtifactError> {
+    ciborium::from_reader(reader).map_err(|e| ArtifactError::Decode(format!("{:?}", e)))
+}
+
+/// Reads a CBOR header and checks its `version` field before decoding the rest of it,
+/// so a file of another version is reported as such even if the header layout has changed.
+pub(crate) fn read_versioned_header<T: DeserializeOwned, R: Read>(
+    reader: &mut R,
+    expected_version: u32,
+) -> Result<T, ArtifactError> {
+    let header: ciborium::Value = read_cbor(reader)?;
+
+    let version = header
+        .as_map()
+        .and_then(|fields| {
+            fields
+                .iter()
+                .find(|(key, _)| key.as_text() == Some("version"))
+                .and_then(|(_, value)| value.as_integer())
+        })
+        .and_then(|version| u32::try_from(version).ok())
+        .ok_or(ArtifactError::Decode(
+            "header has no format version".to_string(),
+        ))?;
+
+    if version != expected_version {
+        return Err(ArtifactError::VersionMismatch {
+            expected: expected_version,
+            found: version,
+        });
+    }
+
+    header
+        .deserialized()
+        .map_err(|e| ArtifactError::Decode(format!("{:?}", e)))
+}
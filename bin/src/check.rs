@@ -0,0 +1,155 @@
+use crate::config::Val;
+use crate::manifest::Manifest;
+use air::{AirConfig, ConstraintKind, LineaAIR, NUM_CHALLENGES};
+use prover::{check_constraints, ConstraintFailure};
+use rand::distributions::Standard;
+use rand::{thread_rng, Rng};
+use std::error::Error;
+use trace::diagnostics::LookupDiagnostics;
+use trace::error::TraceError;
+use trace::tables::RawTables;
+
+/// Unsatisfied arguments found by [`check_inputs`].
+pub struct CheckReport {
+    pub failures: Vec<TraceError>,
+    /// Filled only in the diagnostic mode.
+    pub lookups: Vec<LookupDiagnostics>,
+    /// Failing AIR constraints with the names of their arguments, filled only when the
+    /// constraints are evaluated.
+    pub constraints: Vec<(String, ConstraintFailure)>,
+}
+
+impl CheckReport {
+    pub fn is_satisfied(&self) -> bool {
+        self.failures.is_empty() && self.constraints.is_empty()
+    }
+}
+
+/// Generates the whole trace of the manifest inputs without proving it and returns every
+/// unsatisfied lookup or permutation.
+///
+/// There is no transcript here, so the challenges are just random. With `diagnostics` set,
+/// the A rows missing from B are also listed for every lookup, at most `limit` per lookup.
+/// With `constraints` set, the `LineaAIR` constraints of every table are also evaluated on
+/// every row, as the prover would, and at most `limit` failures per argument are kept.
+pub fn check_inputs(
+    manifest: &Manifest,
+    min_height: usize,
+    diagnostics: bool,
+    constraints: bool,
+    limit: usize,
+) -> Result<CheckReport, Box<dyn Error>> {
+    let (lookup_traces, permutation_traces, range_traces) = manifest.load()?;
+
+    let tables = RawTables::new(
+        permutation_traces,
+        lookup_traces,
+        range_traces,
+        min_height,
+        manifest.log_derivative_permutations,
+    )?;
+
+    let mut rng = thread_rng();
+    let challenges: Vec<Val> = (0..NUM_CHALLENGES)
+        .map(|_| rng.sample(Standard {}))
+        .collect();
+
+    let (failures, constraints) = if constraints {
+        let (aux_traces, failures) = tables.get_unchecked_aux_traces(&challenges)?;
+        let mut constraints = Vec::new();
+
+        for ((table, cfgs), (main, aux)) in tables
+            .tables
+            .iter()
+            .zip(&tables.cfgs)
+            .zip(tables.get_traces().iter().zip(&aux_traces))
+        {
+            let air =
+                LineaAIR::with_preprocessed(cfgs.clone(), table.preprocessed.clone(), table.height);
+            let preprocessed = table.get_preprocessed_trace();
+            let mut counts = vec![0; cfgs.len()];
+            for failure in
+                check_constraints(&air, main, aux, preprocessed.as_ref(), &[], &challenges)
+            {
+                if counts[failure.argument] < limit {
+                    counts[failure.argument] += 1;
+                    constraints.push((failure_label(cfgs, &table.names, &failure), failure));
+                }
+            }
+        }
+
+        (failures, constraints)
+    } else {
+        (tables.check(&challenges), Vec::new())
+    };
+
+    let lookups = if diagnostics {
+        tables.diagnose_lookups(limit)
+    } else {
+        Vec::new()
+    };
+
+    Ok(CheckReport {
+        failures,
+        lookups,
+        constraints,
+    })
+}
+
+/// Name of the argument of a failing constraint. Running sum constraints of an accumulator
+/// shared by several lookups are annotated with its first lookup, so they are labelled with
+/// all the lookups adding into it instead.
+fn failure_label(cfgs: &[AirConfig], names: &[String], failure: &ConstraintFailure) -> String {
+    let running_sum = matches!(
+        failure.kind,
+        ConstraintKind::FirstRow | ConstraintKind::Transition | ConstraintKind::LastRow
+    );
+
+    if let (true, AirConfig::Lookup(owner)) = (running_sum, &cfgs[failure.argument]) {
+        let contributors: Vec<&str> = cfgs
+            .iter()
+            .zip(names)
+            .filter(|(cfg, _)| matches!(cfg, AirConfig::Lookup(l) if l.check_id == owner.check_id))
+            .map(|(_, name)| name.as_str())
+            .collect();
+
+        if contributors.len() > 1 {
+            return format!("shared accumulator ({})", contributors.join(", "));
+        }
+    }
+
+    names[failure.argument].clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p3_field::FieldAlgebra;
+    use trace::testing::lookup;
+    use trace::RawTrace;
+
+    #[test]
+    fn non_boolean_filter_fails_the_filter_constraint() {
+        let lt = lookup("filtered", &[1, 2, 3, 4], &[&[1, 2, 3, 4]]);
+
+        let mut trace = RawTrace::new();
+        let cfgs = trace.push_traces(vec![], vec![lt], vec![]).unwrap();
+        let AirConfig::Lookup(cfg) = &cfgs[0] else {
+            panic!("lookup config expected");
+        };
+        // The loader rejects such filters, so it is only reachable by editing the trace
+        trace.main[trace.width + cfg.a_filter_id] = Val::TWO;
+
+        let mut rng = thread_rng();
+        let challenges: Vec<Val> = (0..NUM_CHALLENGES)
+            .map(|_| rng.sample(Standard {}))
+            .collect();
+        let (aux, _) = trace.get_unchecked_aux_trace(&cfgs, &challenges).unwrap();
+
+        let air = LineaAIR::new(cfgs);
+        let failures = check_constraints(&air, &trace.get_trace(), &aux, None, &[], &challenges);
+        assert!(failures
+            .iter()
+            .any(|f| f.row == 1 && f.argument == 0 && f.kind == ConstraintKind::Filter));
+    }
+}
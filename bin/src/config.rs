@@ -1,14 +1,20 @@
-use p3_bls12_377_fr::{Bls12_377Fr, Poseidon2Bls12337};
+use ark_ff::PrimeField;
+use p3_bls12_377_fr::{Bls12_377Fr, FF_Bls12_377Fr, Poseidon2Bls12337};
 use p3_challenger::HashChallenger;
 use p3_dft::Radix2DitParallel;
-use p3_fri::TwoAdicFriPcs;
+use p3_fri::{FriConfig, TwoAdicFriPcs};
 use p3_merkle_tree::MerkleTreeMmcs;
+use p3_poseidon2::ExternalLayerConstants;
 use p3_symmetric::{CompressionFunctionFromHasher, PaddingFreeSponge};
 use p3_uni_stark::StarkConfig;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
 
 pub type Val = Bls12_377Fr;
 pub type Challenge = Bls12_377Fr;
-pub type Perm = Poseidon2Bls12337<3>;
+pub const POSEIDON2_WIDTH: usize = 3;
+pub type Perm = Poseidon2Bls12337<POSEIDON2_WIDTH>;
 pub type Hash = PaddingFreeSponge<Perm, 3, 2, 1>;
 
 // Use with proper PCS
@@ -23,3 +29,151 @@ pub type Dft = Radix2DitParallel<Val>;
 pub type Challenger = HashChallenger<Val, Hash, 1>;
 pub type Config =
     StarkConfig<TwoAdicFriPcs<Val, Dft, ValMmcs, ChallengeMmcs>, Challenge, Challenger>;
+
+/// Number of external (full) Poseidon2 rounds, split equally into initial and terminal ones.
+pub const POSEIDON2_ROUNDS_F: usize = 8;
+/// Number of internal (partial) Poseidon2 rounds.
+pub const POSEIDON2_ROUNDS_P: usize = 22;
+/// Seed of the ChaCha20 stream the Poseidon2 round constants are derived from.
+pub const POSEIDON2_SEED: [u8; 32] = *b"linea-stark-prover:poseidon2:t=3";
+
+/// Derives the canonical Poseidon2 round constants.
+///
+/// Every constant is 64 consecutive bytes of the ChaCha20 stream seeded with
+/// [`POSEIDON2_SEED`] (zero nonce, counter starting at zero), interpreted as a
+/// little-endian integer and reduced modulo the scalar field order. Constants are
+/// taken in order: initial external rounds, terminal external rounds, internal rounds.
+pub fn poseidon2_round_constants() -> (ExternalLayerConstants<Val, POSEIDON2_WIDTH>, Vec<Val>) {
+    let mut rng = ChaCha20Rng::from_seed(POSEIDON2_SEED);
+    let mut next_constant = || {
+        let mut bytes = [0u8; 64];
+        rng.fill_bytes(&mut bytes);
+        Val::new(FF_Bls12_377Fr::from_le_bytes_mod_order(&bytes))
+    };
+
+    let initial: Vec<[Val; POSEIDON2_WIDTH]> = (0..POSEIDON2_ROUNDS_F / 2)
+        .map(|_| core::array::from_fn(|_| next_constant()))
+        .collect();
+    let terminal: Vec<[Val; POSEIDON2_WIDTH]> = (0..POSEIDON2_ROUNDS_F / 2)
+        .map(|_| core::array::from_fn(|_| next_constant()))
+        .collect();
+    let internal: Vec<Val> = (0..POSEIDON2_ROUNDS_P).map(|_| next_constant()).collect();
+
+    (ExternalLayerConstants::new(initial, terminal), internal)
+}
+
+/// Poseidon2 permutation with the canonical round constants. Prover and verifier
+/// must both build the hash from this instance.
+pub fn default_perm() -> Perm {
+    let (external_constants, internal_constants) = poseidon2_round_constants();
+    Perm::new(external_constants, internal_constants)
+}
+
+/// Poseidon2 parameters the Merkle tree and challenger hash is built from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HashParameters {
+    pub width: usize,
+    pub rounds_f: usize,
+    pub rounds_p: usize,
+    pub seed: [u8; 32],
+}
+
+impl Default for HashParameters {
+    fn default() -> Self {
+        HashParameters {
+            width: POSEIDON2_WIDTH,
+            rounds_f: POSEIDON2_ROUNDS_F,
+            rounds_p: POSEIDON2_ROUNDS_P,
+            seed: POSEIDON2_SEED,
+        }
+    }
+}
+
+/// FRI parameters of the PCS.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FriParameters {
+    pub log_blowup: usize,
+    pub log_final_poly_len: usize,
+    pub num_queries: usize,
+    pub proof_of_work_bits: usize,
+}
+
+impl Default for FriParameters {
+    fn default() -> Self {
+        FriParameters {
+            log_blowup: 3,
+            log_final_poly_len: 0,
+            num_queries: 33,
+            proof_of_work_bits: 0, //29
+        }
+    }
+}
+
+pub fn hash() -> Hash {
+    Hash::new(default_perm())
+}
+
+pub fn new_challenger() -> Challenger {
+    Challenger::new(vec![], hash())
+}
+
+pub fn stark_config(fri_parameters: &FriParameters) -> Config {
+    let hash = hash();
+
+    let dft = Dft::default();
+
+    // TODO: use proper PCS configured with FRI config
+    let compress = Compress::new(hash.clone());
+    let val_mmcs = ValMmcs::new(hash.clone(), compress.clone());
+    let challenge_mmcs = ChallengeMmcs::new(hash.clone(), compress.clone());
+    let fri_config = FriConfig {
+        log_blowup: fri_parameters.log_blowup,
+        log_final_poly_len: fri_parameters.log_final_poly_len,
+        num_queries: fri_parameters.num_queries,
+        proof_of_work_bits: fri_parameters.proof_of_work_bits,
+        mmcs: challenge_mmcs,
+    };
+
+    let pcs = TwoAdicFriPcs::new(dft, val_mmcs, fri_config);
+
+    Config::new(pcs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn val(s: &str) -> Val {
+        Val::new(FF_Bls12_377Fr::from_str(s).unwrap())
+    }
+
+    #[test]
+    fn poseidon2_round_constants_known_answer() {
+        let (external_constants, internal_constants) = poseidon2_round_constants();
+
+        let initial = external_constants.get_initial_constants();
+        let terminal = external_constants.get_terminal_constants();
+
+        assert_eq!(initial.len(), POSEIDON2_ROUNDS_F / 2);
+        assert_eq!(terminal.len(), POSEIDON2_ROUNDS_F / 2);
+        assert_eq!(internal_constants.len(), POSEIDON2_ROUNDS_P);
+
+        assert_eq!(
+            initial[0][0],
+            val("527361479393327710730747081340261545297109791625571939782866735761318641419")
+        );
+        assert_eq!(
+            terminal[0][0],
+            val("5212857903163804253234907746740496935431458119771499033730380618063025664697")
+        );
+        assert_eq!(
+            internal_constants[0],
+            val("1364631711447149331662982776043901503616929777781696434030453037703850984870")
+        );
+        assert_eq!(
+            internal_constants[POSEIDON2_ROUNDS_P - 1],
+            val("2044038790693244929338728865451735518736878549547982641422165443469396228061")
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::config::{FriParameters, Val};
+use crate::manifest::Manifest;
+use air::{AirConfig, LineaAIR, MultiStageAir};
+use p3_air::BaseAir;
+use prover::get_log_quotient_degree;
+use std::error::Error;
+use trace::argument_layouts;
+use trace::lookup::RawLookupTrace;
+use trace::permutation::RawPermutationTrace;
+use trace::range::RawRangeTrace;
+use trace::tables::RawTables;
+
+/// Committing cost measured on the README benchmark: 14 trace and 8 quotient columns of
+/// 2^19 rows with blowup 8 took ~342s.
+const SECONDS_PER_MILLION_LDE_CELLS: f64 = 3.7;
+
+/// Prints the layout of the manifest inputs, the tables they are grouped into and a rough
+/// proving cost estimate, without decoding the traces. With `columns`, also lists the main
+/// trace columns of every table and the arguments referencing them.
+pub fn inspect_inputs(
+    manifest: &Manifest,
+    min_height: usize,
+    columns: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (lookup_traces, permutation_traces, range_traces) = manifest.load()?;
+    let log_derivative_permutations = manifest.log_derivative_permutations;
+
+    // Tables as grouped and laid out by `RawTables`
+    let tables = RawTables::layouts(
+        &permutation_traces,
+        &lookup_traces,
+        &range_traces,
+        min_height,
+        log_derivative_permutations,
+    );
+
+    println!(
+        "{:<60} {:>12} {:>8} {:>10} {:>10} {:>10}",
+        "argument", "kind", "height", "columns", "main", "aux"
+    );
+
+    let lookups: Vec<&RawLookupTrace> = lookup_traces.iter().collect();
+    let permutations: Vec<&RawPermutationTrace> = permutation_traces.iter().collect();
+    let ranges: Vec<&RawRangeTrace> = range_traces.iter().collect();
+
+    // Kind, height and column counts of every argument in the `argument_layouts` order
+    let shapes = lookup_traces
+        .iter()
+        .map(|lookup| {
+            (
+                "lookup",
+                lookup.get_max_height(),
+                format!(
+                    "{}/{}x{}",
+                    lookup.a.len(),
+                    lookup.b.len(),
+                    lookup.b[0].len()
+                ),
+            )
+        })
+        .chain(permutation_traces.iter().map(|permutation| {
+            (
+                "permutation",
+                permutation.get_max_height(),
+                format!("{}/{}", permutation.a.len(), permutation.b.len()),
+            )
+        }))
+        .chain(range_traces.iter().map(|range| {
+            (
+                "range",
+                range.get_max_height(),
+                format!("{} bits", range.bits),
+            )
+        }));
+
+    let layouts = argument_layouts(
+        &lookups,
+        &permutations,
+        &ranges,
+        log_derivative_permutations,
+    );
+    for ((name, cfg, _, _), (kind, height, columns)) in layouts.zip(shapes) {
+        println!(
+            "{:<60} {:>12} {:>8} {:>10} {:>10} {:>10}",
+            name,
+            kind,
+            height,
+            columns,
+            cfg.main_width(),
+            cfg.aux_width()
+        );
+    }
+
+    // Range checks heightened by their range tables
+    for range in &range_traces {
+        if range.get_max_height() > range.column.len() {
+            println!(
+                "note: {} has {} rows, but its {}-bit range table takes {} rows of its table",
+                range.name,
+                range.column.len(),
+                range.limb_bits()[0],
+                range.get_max_height()
+            );
+        }
+    }
+
+    println!();
+    println!(
+        "{:<8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>16}",
+        "table", "height", "fixed", "main", "aux", "quotient", "LDE cells"
+    );
+
+    let log_blowup = FriParameters::default().log_blowup;
+    let mut total_lde_cells = 0;
+
+    for (i, (table, cfgs)) in tables.tables.iter().zip(&tables.cfgs).enumerate() {
+        let height = table.height;
+        let air = LineaAIR::with_preprocessed(cfgs.clone(), table.preprocessed.clone(), height);
+        let fixed_width = <LineaAIR as MultiStageAir<Val>>::preprocessed_width(&air);
+        let width = <LineaAIR as BaseAir<Val>>::width(&air);
+        let aux_width = <LineaAIR as MultiStageAir<Val>>::aux_width(&air);
+        let quotient_degree = 1 << get_log_quotient_degree::<Val, LineaAIR>(&air, 0);
+
+        // Preprocessed columns are committed at setup, but still extended and opened
+        let lde_cells =
+            (height << log_blowup) * (fixed_width + width + aux_width + quotient_degree);
+        total_lde_cells += lde_cells;
+
+        println!(
+            "{:<8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>16}",
+            i, height, fixed_width, width, aux_width, quotient_degree, lde_cells
+        );
+    }
+
+    if columns {
+        println!();
+        println!(
+            "{:<8} {:>8} {:<40} {}",
+            "table", "column", "name", "arguments"
+        );
+
+        for (i, (table, cfgs)) in tables.tables.iter().zip(&tables.cfgs).enumerate() {
+            for id in 0..table.width {
+                let users: Vec<(&str, &str)> = cfgs
+                    .iter()
+                    .zip(&table.names)
+                    .filter_map(|(cfg, name)| Some((cfg.main_column_name(id)?, name.as_str())))
+                    .collect();
+                let arguments: Vec<&str> = users.iter().map(|(_, name)| *name).collect();
+                println!(
+                    "{:<8} {:>8} {:<40} {}",
+                    i,
+                    id,
+                    users.first().map_or("", |(column, _)| *column),
+                    arguments.join(", ")
+                );
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "arguments:       {}",
+        lookup_traces.len() + permutation_traces.len() + range_traces.len()
+    );
+    println!("tables:          {}", tables.tables.len());
+    println!("LDE cells:       {}", total_lde_cells);
+    println!(
+        "estimated time:  ~{:.0}s",
+        total_lde_cells as f64 / 1e6 * SECONDS_PER_MILLION_LDE_CELLS
+    );
+
+    Ok(())
+}
@@ -1,97 +1,238 @@
+mod artifact;
+mod check;
 mod config;
-
-use crate::config::*;
-use air::LineaAIR;
-use p3_field::Field;
-use p3_fri::{FriConfig, TwoAdicFriPcs};
-use p3_uni_stark::{prove, verify};
-use rand::distributions::Standard;
-use rand::{thread_rng, Rng};
-use std::collections::HashSet;
-use std::fmt::Debug;
-use trace::{lookup::RawLookupTrace, permutation::RawPermutationTrace, RawTrace};
+mod inspect;
+mod manifest;
+mod proof;
+mod prove;
+mod verify;
+mod vk;
+
+use crate::check::check_inputs;
+use crate::inspect::inspect_inputs;
+use crate::manifest::Manifest;
+use crate::prove::prove_inputs;
+use crate::verify::{verify_proof_file, VerifyError};
+use clap::{Args, Parser, Subcommand};
+use std::error::Error;
+use std::process::ExitCode;
+use trace::error::TraceError;
 use tracing_forest::util::LevelFilter;
 use tracing_forest::ForestLayer;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
-fn main() -> Result<(), impl Debug> {
-    let env_filter = EnvFilter::builder()
-        .with_default_directive(LevelFilter::INFO.into())
-        .from_env_lossy();
-
-    Registry::default()
-        .with(env_filter)
-        .with(ForestLayer::default())
-        .init();
-
-    let mut rng = thread_rng();
-    let alpha_challenge = rng.sample(Standard {});
-    let delta_challenge = rng.sample(Standard {});
-    println!("Challenge delta: {}", delta_challenge);
-    println!("Challenge alpha: {}", alpha_challenge);
-
-    let mut raw_trace = RawTrace::new(vec![alpha_challenge, delta_challenge]);
-
-    let lookup_traces = vec![
-        RawLookupTrace::read_file("../lookup_0.bin"),
-    ];
-
-    let permutation_traces = vec![];
-
-    let cfgs = raw_trace.push_traces(permutation_traces, lookup_traces);
-
-    // -----------------------------------------------------------
+const PROOF_PATH: &str = "proof.bin";
+const VK_PATH: &str = "vk.bin";
 
-    // TODO: should not be just random
+/// Exit code when the proof is rejected or the constraints are not satisfied.
+const EXIT_INVALID: u8 = 1;
+/// Exit code when the inputs can't be read or decoded. Also used by clap on usage errors.
+const EXIT_BAD_INPUT: u8 = 2;
 
-    let perm = Perm::new_from_rng(8, 22, &mut rng);
-    let hash = Hash::new(perm.clone());
+#[derive(Parser)]
+#[command(about = "Plonky3 Linea prover")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    let dft = Dft::default();
+#[derive(Subcommand)]
+enum Command {
+    /// Prove the inputs and write the proof and the verifying key.
+    Prove {
+        #[command(flatten)]
+        inputs: InputArgs,
+        /// Output proof file.
+        #[arg(long, default_value = PROOF_PATH)]
+        proof: String,
+        /// Output verifying key file.
+        #[arg(long, default_value = VK_PATH)]
+        vk: String,
+    },
+    /// Verify a proof against a verifying key.
+    Verify {
+        /// Proof file.
+        #[arg(long, default_value = PROOF_PATH)]
+        proof: String,
+        /// Verifying key file.
+        #[arg(long, default_value = VK_PATH)]
+        vk: String,
+    },
+    /// Generate the trace and check the constraints without proving.
+    Check {
+        #[command(flatten)]
+        inputs: InputArgs,
+        /// List the lookup rows that are missing from B.
+        #[arg(long)]
+        diagnose: bool,
+        /// Also evaluate the AIR constraints on every row, as the prover would.
+        #[arg(long)]
+        constraints: bool,
+        /// Maximum number of listed rows per lookup or failing constraints per argument.
+        #[arg(long, default_value_t = 20)]
+        max_rows: usize,
+    },
+    /// Print the inputs layout and the estimated proving cost.
+    Inspect {
+        #[command(flatten)]
+        inputs: InputArgs,
+        /// Also list the main trace columns of every table with their names.
+        #[arg(long)]
+        columns: bool,
+    },
+}
 
-    // TODO: use proper PCS configured with FRI config
-    let compress = Compress::new(hash.clone());
-    let val_mmcs = ValMmcs::new(hash.clone(), compress.clone());
-    let challenge_mmcs = ChallengeMmcs::new(hash.clone(), compress.clone());
-    let fri_config = FriConfig {
-        log_blowup: 3,
-        log_final_poly_len: 0,
-        num_queries: 33,
-        proof_of_work_bits: 0, //29
-        mmcs: challenge_mmcs,
-    };
+#[derive(Args)]
+struct InputArgs {
+    /// TOML or JSON manifest listing the inputs.
+    #[arg(long)]
+    manifest: Option<String>,
+    /// Lookup trace file or glob pattern, may be repeated.
+    #[arg(long)]
+    lookup: Vec<String>,
+    /// Permutation trace file or glob pattern, may be repeated.
+    #[arg(long)]
+    permutation: Vec<String>,
+    /// Range check trace file or glob pattern, may be repeated.
+    #[arg(long)]
+    range: Vec<String>,
+    /// Minimum trace height, the trace is padded to a power of two not less than it.
+    #[arg(long, default_value_t = 1)]
+    min_height: usize,
+    /// Prove permutations as lookups sharing the log-derivative running sum, also enabled
+    /// by the manifest.
+    #[arg(long)]
+    log_derivative_permutations: bool,
+}
 
-    let pcs = TwoAdicFriPcs::new(dft, val_mmcs, fri_config);
+impl InputArgs {
+    fn manifest(&self) -> Result<Manifest, Box<dyn Error>> {
+        let mut manifest = Manifest::from_inputs(
+            self.manifest.as_deref(),
+            &self.lookup,
+            &self.permutation,
+            &self.range,
+        )?;
+        manifest.log_derivative_permutations |= self.log_derivative_permutations;
+        Ok(manifest)
+    }
+}
 
-    let config = Config::new(pcs);
+fn main() -> ExitCode {
+    let cli = Cli::parse();
 
-    println!("Generating trace...");
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
 
-    let t = raw_trace.get_trace();
+    Registry::default()
+        .with(env_filter)
+        .with(ForestLayer::default())
+        .init();
 
-    println!("Creating LineaAir...");
+    match cli.command {
+        Command::Prove { inputs, proof, vk } => report(
+            inputs
+                .manifest()
+                .and_then(|m| prove_inputs(&m, inputs.min_height, &proof, &vk)),
+        ),
+        Command::Verify { proof, vk } => match verify_proof_file(&proof, &vk) {
+            Ok(()) => {
+                println!("Proof is valid");
+                ExitCode::SUCCESS
+            }
+            Err(e @ (VerifyError::Proof(_) | VerifyError::VerifyingKey(_))) => {
+                eprintln!("Error: {}", e);
+                ExitCode::from(EXIT_BAD_INPUT)
+            }
+            Err(e) => {
+                eprintln!("Verification failed: {}", e);
+                ExitCode::from(EXIT_INVALID)
+            }
+        },
+        Command::Check {
+            inputs,
+            diagnose,
+            constraints,
+            max_rows,
+        } => match inputs
+            .manifest()
+            .and_then(|m| check_inputs(&m, inputs.min_height, diagnose, constraints, max_rows))
+        {
+            Ok(outcome) => {
+                outcome.lookups.iter().for_each(|d| eprintln!("{}", d));
+                if outcome.is_satisfied() {
+                    println!("All constraints are satisfied");
+                    ExitCode::SUCCESS
+                } else {
+                    outcome.failures.iter().for_each(|e| eprintln!("{}", e));
+                    outcome
+                        .constraints
+                        .iter()
+                        .for_each(|(name, failure)| eprintln!("{}: {}", name, failure));
+                    ExitCode::from(EXIT_INVALID)
+                }
+            }
+            Err(e) => report(Err(e)),
+        },
+        Command::Inspect { inputs, columns } => report(
+            inputs
+                .manifest()
+                .and_then(|m| inspect_inputs(&m, inputs.min_height, columns)),
+        ),
+    }
+}
 
-    let air = LineaAIR::new(cfgs);
+fn report(result: Result<(), Box<dyn Error>>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(exit_code(e.as_ref()))
+        }
+    }
+}
 
-    let mut challenger = Challenger::new(vec![], hash.clone());
-    println!("Proving...");
-    let proof = prove(
-        &config,
-        &air,
-        &mut challenger,
-        t,
-        &vec![alpha_challenge, delta_challenge],
-    );
+/// Exit code of a failed command: unsatisfied arguments are invalid, any other error means
+/// the inputs couldn't be used.
+fn exit_code(error: &(dyn Error + 'static)) -> u8 {
+    match error.downcast_ref::<TraceError>() {
+        Some(TraceError::UnsatisfiedLookup { .. } | TraceError::UnsatisfiedPermutation { .. }) => {
+            EXIT_INVALID
+        }
+        _ => EXIT_BAD_INPUT,
+    }
+}
 
-    let mut challenger = Challenger::new(vec![], hash.clone());
-    println!("Verification...");
-    verify(
-        &config,
-        &air,
-        &mut challenger,
-        &proof,
-        &vec![alpha_challenge, delta_challenge],
-    )
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FriParameters;
+    use crate::prove::prove_tables;
+    use trace::tables::RawTables;
+    use trace::testing::lookup;
+
+    #[test]
+    fn unsatisfied_inputs_exit_as_invalid() {
+        let tables = RawTables::new(
+            vec![],
+            vec![lookup("missing", &[5], &[&[1]])],
+            vec![],
+            1,
+            false,
+        )
+        .unwrap();
+        let error = prove_tables(&tables, FriParameters::default())
+            .err()
+            .unwrap();
+        assert_eq!(exit_code(error.as_ref()), EXIT_INVALID);
+
+        let error: Box<dyn Error> = Box::new(TraceError::Decode {
+            path: "missing.bin".to_string(),
+            message: "unexpected end of input".to_string(),
+        });
+        assert_eq!(exit_code(error.as_ref()), EXIT_BAD_INPUT);
+    }
 }
@@ -0,0 +1,383 @@
+use serde::Deserialize;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use trace::error::TraceError;
+use trace::lookup::RawLookupTrace;
+use trace::permutation::RawPermutationTrace;
+use trace::range::RawRangeTrace;
+
+/// Lookups, permutations and range checks read from a manifest.
+pub type Inputs = (
+    Vec<RawLookupTrace>,
+    Vec<RawPermutationTrace>,
+    Vec<RawRangeTrace>,
+);
+
+/// List of the exported constraints to prove.
+///
+/// ```toml
+/// [[lookup]]
+/// path = "exports/lookup_*.bin"
+///
+/// [[permutation]]
+/// path = "exports/mxp_perm.bin"
+/// name = "mxp.perm"
+///
+/// [[range]]
+/// path = "exports/range_*.bin"
+/// ```
+///
+/// Arguments are proved in manifest order; files matched by one glob pattern are
+/// taken in lexicographic order. With `log_derivative_permutations = true`, permutations
+/// are proved as lookups sharing one running sum per table with the other lookups.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub lookup: Vec<InputEntry>,
+    #[serde(default)]
+    pub permutation: Vec<InputEntry>,
+    #[serde(default)]
+    pub range: Vec<InputEntry>,
+    #[serde(default)]
+    pub log_derivative_permutations: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InputEntry {
+    /// File path or glob pattern. Relative paths are resolved against the manifest directory.
+    pub path: String,
+    /// Disabled entries are skipped.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Don't fail if the pattern matches no file.
+    #[serde(default)]
+    pub optional: bool,
+    /// Overrides the argument name stored in the file. Only allowed for a single file.
+    pub name: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl InputEntry {
+    pub fn new(path: &str) -> Self {
+        InputEntry {
+            path: path.to_string(),
+            enabled: true,
+            optional: false,
+            name: None,
+        }
+    }
+}
+
+pub enum ManifestError {
+    Io(String, std::io::Error),
+    Parse(String, String),
+    Pattern(String, String),
+    NoMatch(String),
+    AmbiguousName(String),
+    Trace(TraceError),
+    NoInputs,
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Io(path, e) => write!(f, "{}: {}", path, e),
+            ManifestError::Parse(path, e) => write!(f, "failed to parse manifest {}: {}", path, e),
+            ManifestError::Pattern(pattern, e) => {
+                write!(f, "invalid input pattern {}: {}", pattern, e)
+            }
+            ManifestError::NoMatch(pattern) => write!(f, "no input file matches {}", pattern),
+            ManifestError::AmbiguousName(pattern) => write!(
+                f,
+                "name override for {} is only allowed for a single file",
+                pattern
+            ),
+            ManifestError::Trace(e) => write!(f, "{}", e),
+            ManifestError::NoInputs => {
+                write!(f, "no lookup, permutation or range check inputs given")
+            }
+        }
+    }
+}
+
+impl Debug for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl Manifest {
+    /// Reads a JSON (`.json` extension) or TOML manifest.
+    pub fn read_file(path: &str) -> Result<Self, ManifestError> {
+        let content =
+            fs::read_to_string(path).map_err(|e| ManifestError::Io(path.to_string(), e))?;
+
+        let mut manifest: Manifest = if path.ends_with(".json") {
+            serde_json::from_str(&content)
+                .map_err(|e| ManifestError::Parse(path.to_string(), e.to_string()))?
+        } else {
+            toml::from_str(&content)
+                .map_err(|e| ManifestError::Parse(path.to_string(), e.to_string()))?
+        };
+
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        manifest
+            .lookup
+            .iter_mut()
+            .chain(manifest.permutation.iter_mut())
+            .chain(manifest.range.iter_mut())
+            .for_each(|entry| {
+                if Path::new(&entry.path).is_relative() {
+                    entry.path = base.join(&entry.path).to_string_lossy().into_owned();
+                }
+            });
+
+        Ok(manifest)
+    }
+
+    /// Builds the manifest from an optional manifest file and single input files given
+    /// on the command line. Single inputs are appended after the manifest ones.
+    pub fn from_inputs(
+        manifest: Option<&str>,
+        lookups: &[String],
+        permutations: &[String],
+        ranges: &[String],
+    ) -> Result<Self, ManifestError> {
+        let mut manifest = match manifest {
+            Some(path) => Manifest::read_file(path)?,
+            None => Manifest::default(),
+        };
+
+        manifest
+            .lookup
+            .extend(lookups.iter().map(|path| InputEntry::new(path)));
+        manifest
+            .permutation
+            .extend(permutations.iter().map(|path| InputEntry::new(path)));
+        manifest
+            .range
+            .extend(ranges.iter().map(|path| InputEntry::new(path)));
+
+        Ok(manifest)
+    }
+
+    /// Reads all enabled inputs in manifest order.
+    pub fn load(&self) -> Result<Inputs, ManifestError> {
+        let mut lookup_traces = Vec::new();
+        for entry in &self.lookup {
+            for path in entry.resolve()? {
+                let mut lookup = RawLookupTrace::read_file(&path.to_string_lossy())
+                    .map_err(ManifestError::Trace)?;
+                if let Some(name) = &entry.name {
+                    lookup.name = name.clone();
+                }
+                lookup_traces.push(lookup);
+            }
+        }
+
+        let mut permutation_traces = Vec::new();
+        for entry in &self.permutation {
+            for path in entry.resolve()? {
+                let mut permutation = RawPermutationTrace::read_file(&path.to_string_lossy())
+                    .map_err(ManifestError::Trace)?;
+                if let Some(name) = &entry.name {
+                    permutation.name = name.clone();
+                }
+                permutation_traces.push(permutation);
+            }
+        }
+
+        let mut range_traces = Vec::new();
+        for entry in &self.range {
+            for path in entry.resolve()? {
+                let mut range = RawRangeTrace::read_file(&path.to_string_lossy())
+                    .map_err(ManifestError::Trace)?;
+                if let Some(name) = &entry.name {
+                    range.name = name.clone();
+                }
+                range_traces.push(range);
+            }
+        }
+
+        if lookup_traces.is_empty() && permutation_traces.is_empty() && range_traces.is_empty() {
+            return Err(ManifestError::NoInputs);
+        }
+
+        Ok((lookup_traces, permutation_traces, range_traces))
+    }
+}
+
+impl InputEntry {
+    /// Expands the entry path into the sorted list of matching files.
+    fn resolve(&self) -> Result<Vec<PathBuf>, ManifestError> {
+        if !self.enabled {
+            return Ok(vec![]);
+        }
+
+        let mut paths = glob::glob(&self.path)
+            .map_err(|e| ManifestError::Pattern(self.path.clone(), e.to_string()))?
+            .collect::<Result<Vec<PathBuf>, _>>()
+            .map_err(|e| ManifestError::Pattern(self.path.clone(), e.to_string()))?;
+        paths.sort();
+
+        if paths.is_empty() && !self.optional {
+            return Err(ManifestError::NoMatch(self.path.clone()));
+        }
+
+        if self.name.is_some() && paths.len() > 1 {
+            return Err(ManifestError::AmbiguousName(self.path.clone()));
+        }
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use trace::testing::{lookup, permutation};
+
+    /// Directory of the manifest and its inputs, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+            fs::create_dir_all(path.join("exports")).unwrap();
+            TempDir(path)
+        }
+
+        fn write<T: Serialize>(&self, relative: &str, input: &T) {
+            let file = fs::File::create(self.0.join(relative)).unwrap();
+            ciborium::into_writer(input, file).unwrap();
+        }
+
+        fn write_manifest(&self, file_name: &str, content: &str) -> String {
+            let path = self.0.join(file_name);
+            fs::write(&path, content).unwrap();
+            path.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn names(inputs: &Inputs) -> Vec<&str> {
+        let (lookups, permutations, ranges) = inputs;
+        lookups
+            .iter()
+            .map(|lt| lt.name.as_str())
+            .chain(permutations.iter().map(|pt| pt.name.as_str()))
+            .chain(ranges.iter().map(|rt| rt.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn toml_globs_are_resolved_against_the_manifest() {
+        let dir = TempDir::new("manifest_toml");
+        dir.write("exports/lookup_b.bin", &lookup("b", &[1], &[&[1]]));
+        dir.write("exports/lookup_a.bin", &lookup("a", &[1], &[&[1]]));
+        let path = dir.write_manifest(
+            "manifest.toml",
+            "[[lookup]]\npath = \"exports/lookup_*.bin\"\n",
+        );
+
+        let manifest = Manifest::read_file(&path).unwrap();
+        assert_eq!(
+            Path::new(&manifest.lookup[0].path),
+            dir.0.join("exports/lookup_*.bin")
+        );
+        assert!(!manifest.log_derivative_permutations);
+
+        // Matches are taken in lexicographic order of their paths
+        assert_eq!(names(&manifest.load().unwrap()), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn json_names_override_the_file_names() {
+        let dir = TempDir::new("manifest_json");
+        dir.write("exports/perm.bin", &permutation("stored", &[1, 2], &[2, 1]));
+        let path = dir.write_manifest(
+            "manifest.json",
+            r#"{
+                "permutation": [{ "path": "exports/perm.bin", "name": "renamed" }],
+                "log_derivative_permutations": true
+            }"#,
+        );
+
+        let manifest = Manifest::read_file(&path).unwrap();
+        assert!(manifest.log_derivative_permutations);
+        assert_eq!(names(&manifest.load().unwrap()), vec!["renamed"]);
+    }
+
+    #[test]
+    fn name_override_of_several_files_is_ambiguous() {
+        let dir = TempDir::new("manifest_ambiguous");
+        dir.write("exports/lookup_a.bin", &lookup("a", &[1], &[&[1]]));
+        dir.write("exports/lookup_b.bin", &lookup("b", &[1], &[&[1]]));
+        let path = dir.write_manifest(
+            "manifest.toml",
+            "[[lookup]]\npath = \"exports/lookup_*.bin\"\nname = \"renamed\"\n",
+        );
+
+        assert!(matches!(
+            Manifest::read_file(&path).unwrap().load(),
+            Err(ManifestError::AmbiguousName(_))
+        ));
+    }
+
+    #[test]
+    fn optional_and_disabled_entries_may_be_missing() {
+        let dir = TempDir::new("manifest_optional");
+        dir.write("exports/lookup.bin", &lookup("present", &[1], &[&[1]]));
+        let path = dir.write_manifest(
+            "manifest.toml",
+            r#"
+            [[lookup]]
+            path = "exports/lookup.bin"
+
+            [[lookup]]
+            path = "exports/missing_*.bin"
+            optional = true
+
+            [[permutation]]
+            path = "exports/missing.bin"
+            enabled = false
+            "#,
+        );
+        let manifest = Manifest::read_file(&path).unwrap();
+        assert_eq!(names(&manifest.load().unwrap()), vec!["present"]);
+
+        // Required entries must match, and something has to be proved
+        let mut required = manifest.clone();
+        required.permutation[0].enabled = true;
+        assert!(matches!(required.load(), Err(ManifestError::NoMatch(_))));
+
+        let mut empty = manifest;
+        empty.lookup.remove(0);
+        assert!(matches!(empty.load(), Err(ManifestError::NoInputs)));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let dir = TempDir::new("manifest_unknown");
+        let path = dir.write_manifest("manifest.toml", "[[lookup]]\npaths = \"a.bin\"\n");
+
+        assert!(matches!(
+            Manifest::read_file(&path),
+            Err(ManifestError::Parse(..))
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::artifact::{read_cbor, read_versioned_header, write_cbor, ArtifactError};
+use crate::config::{Config, FriParameters, HashParameters};
+use prover::Proof;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+/// Version of the proof file layout. Bump on any change of `ProofHeader` or `Proof`.
+pub const PROOF_FORMAT_VERSION: u32 = 3;
+
+/// Header stored in front of the proof.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProofHeader {
+    pub version: u32,
+    pub fri_parameters: FriParameters,
+    pub hash_parameters: HashParameters,
+    /// Names of the proved arguments in the order of the table configs.
+    pub arguments: Vec<String>,
+}
+
+impl ProofHeader {
+    pub fn new(fri_parameters: FriParameters, arguments: Vec<String>) -> Self {
+        ProofHeader {
+            version: PROOF_FORMAT_VERSION,
+            fri_parameters,
+            hash_parameters: HashParameters::default(),
+            arguments,
+        }
+    }
+}
+
+/// Writes the header and the proof as two consecutive CBOR items, so the header can be
+/// checked before decoding a proof of a possibly different layout.
+pub fn write_proof(
+    path: &str,
+    header: &ProofHeader,
+    proof: &Proof<Config>,
+) -> Result<(), ArtifactError> {
+    let file = File::create(path).map_err(|e| ArtifactError::Io(path.to_string(), e))?;
+    let mut writer = BufWriter::new(file);
+
+    write_cbor(header, &mut writer)?;
+    write_cbor(proof, &mut writer)
+}
+
+pub fn read_proof(path: &str) -> Result<(ProofHeader, Proof<Config>), ArtifactError> {
+    let file = File::open(path).map_err(|e| ArtifactError::Io(path.to_string(), e))?;
+    let mut reader = BufReader::new(file);
+
+    let header: ProofHeader = read_versioned_header(&mut reader, PROOF_FORMAT_VERSION)?;
+
+    if header.hash_parameters != HashParameters::default() {
+        return Err(ArtifactError::HashParametersMismatch);
+    }
+
+    let proof: Proof<Config> = read_cbor(&mut reader)?;
+
+    Ok((header, proof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prove::prove_tables;
+    use trace::tables::RawTables;
+    use trace::testing::lookup;
+
+    fn small_proof() -> (ProofHeader, Proof<Config>) {
+        let tables = RawTables::new(
+            vec![],
+            vec![lookup("lookup", &[2, 1], &[&[1, 2]])],
+            vec![],
+            1,
+            false,
+        )
+        .unwrap();
+        let (header, proof, _) = prove_tables(&tables, FriParameters::default()).unwrap();
+        (header, proof)
+    }
+
+    /// Writes the proof to a temporary file and reads it back.
+    fn write_and_read(
+        name: &str,
+        header: &ProofHeader,
+        proof: &Proof<Config>,
+    ) -> Result<(ProofHeader, Proof<Config>), ArtifactError> {
+        let path = std::env::temp_dir().join(format!("{}_{}.bin", name, std::process::id()));
+        let path = path.to_str().unwrap();
+        write_proof(path, header, proof).unwrap();
+        let result = read_proof(path);
+        std::fs::remove_file(path).unwrap();
+        result
+    }
+
+    fn encode(proof: &Proof<Config>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_cbor(proof, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn proof_survives_a_round_trip() {
+        let (header, proof) = small_proof();
+        let (read_header, read_proof) =
+            write_and_read("proof_round_trip", &header, &proof).unwrap();
+
+        assert_eq!(read_header, header);
+        assert_eq!(encode(&read_proof), encode(&proof));
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let (mut header, proof) = small_proof();
+        header.version = PROOF_FORMAT_VERSION + 1;
+
+        assert!(matches!(
+            write_and_read("proof_version", &header, &proof),
+            Err(ArtifactError::VersionMismatch { found, .. }) if found == PROOF_FORMAT_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::{stark_config, Config, FriParameters};
+use crate::manifest::Manifest;
+use crate::proof::{write_proof, ProofHeader};
+use crate::vk::{write_verifying_key, TableLayout, VerifyingKey};
+use air::LineaAIR;
+use prover::{prove, setup, Proof};
+use std::error::Error;
+use trace::tables::RawTables;
+
+/// Proves all the manifest inputs and writes the proof and the verifying key.
+pub fn prove_inputs(
+    manifest: &Manifest,
+    min_height: usize,
+    proof_path: &str,
+    vk_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let (lookup_traces, permutation_traces, range_traces) = manifest.load()?;
+
+    let tables = RawTables::new(
+        permutation_traces,
+        lookup_traces,
+        range_traces,
+        min_height,
+        manifest.log_derivative_permutations,
+    )?;
+
+    let (header, proof, vk) = prove_tables(&tables, FriParameters::default())?;
+
+    println!("Writing proof to {}...", proof_path);
+    write_proof(proof_path, &header, &proof)?;
+
+    println!("Writing verifying key to {}...", vk_path);
+    write_verifying_key(vk_path, &vk)?;
+
+    Ok(())
+}
+
+/// Proves the tables and returns the proof with its header, and the verifying key of the
+/// table layouts.
+pub fn prove_tables(
+    tables: &RawTables,
+    fri_parameters: FriParameters,
+) -> Result<(ProofHeader, Proof<Config>, VerifyingKey), Box<dyn Error>> {
+    let arguments = tables.names();
+    let config = stark_config(&fri_parameters);
+
+    println!("Generating trace...");
+
+    let traces = tables.get_traces();
+
+    println!("Creating LineaAir...");
+
+    let layouts: Vec<TableLayout> = tables
+        .tables
+        .iter()
+        .zip(&tables.cfgs)
+        .map(|(table, cfgs)| {
+            TableLayout::new(cfgs.clone(), table.preprocessed.clone(), table.height)
+        })
+        .collect();
+    let airs: Vec<LineaAIR> = layouts.iter().map(TableLayout::air).collect();
+
+    println!("Committing preprocessed columns...");
+    let preprocessed = setup(&config, &airs);
+
+    let vk = VerifyingKey::new(
+        fri_parameters.clone(),
+        arguments.clone(),
+        layouts,
+        preprocessed.as_ref().map(|p| p.commitment.clone()),
+    );
+
+    let mut challenger = vk.challenger();
+    println!("Proving...");
+    let proof = prove(
+        &config,
+        &airs,
+        preprocessed.as_ref(),
+        &mut challenger,
+        traces,
+        |challenges| tables.get_aux_traces(challenges),
+        &vec![],
+    )?;
+
+    Ok((ProofHeader::new(fri_parameters, arguments), proof, vk))
+}
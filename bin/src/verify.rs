@@ -0,0 +1,252 @@
+use crate::artifact::ArtifactError;
+use crate::config::{stark_config, Config, Val};
+use crate::proof::{read_proof, ProofHeader};
+use crate::vk::{read_verifying_key, VerifyingKey};
+use air::{LineaAIR, MultiStageAir};
+use p3_air::BaseAir;
+use p3_uni_stark::{PcsError, VerificationError};
+use prover::{setup, verify, Proof};
+use std::fmt::{Debug, Display, Formatter};
+
+pub enum VerifyError {
+    Proof(ArtifactError),
+    VerifyingKey(ArtifactError),
+    FriParametersMismatch,
+    LayoutMismatch(String),
+    Verification(VerificationError<PcsError<Config>>),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Proof(e) => write!(f, "invalid proof file: {}", e),
+            VerifyError::VerifyingKey(e) => write!(f, "invalid verifying key: {}", e),
+            VerifyError::FriParametersMismatch => {
+                write!(f, "proof FRI parameters differ from the verifying key ones")
+            }
+            VerifyError::LayoutMismatch(e) => write!(f, "verifying key layout mismatch: {}", e),
+            VerifyError::Verification(VerificationError::InvalidProofShape) => {
+                write!(f, "proof shape doesn't match the verifying key layout")
+            }
+            VerifyError::Verification(VerificationError::InvalidOpeningArgument(e)) => {
+                write!(f, "invalid PCS opening: {:?}", e)
+            }
+            VerifyError::Verification(VerificationError::OodEvaluationMismatch) => write!(
+                f,
+                "constraints evaluated at the out-of-domain point don't match the quotient"
+            ),
+        }
+    }
+}
+
+impl Debug for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Verifies a proof file against a verifying key file, without access to the traces.
+pub fn verify_proof_file(proof_path: &str, vk_path: &str) -> Result<(), VerifyError> {
+    let vk = read_verifying_key(vk_path).map_err(VerifyError::VerifyingKey)?;
+    let (header, proof) = read_proof(proof_path).map_err(VerifyError::Proof)?;
+
+    verify_proof(&vk, &header, &proof)
+}
+
+/// Verifies a proof against the table layouts of the verifying key.
+///
+/// The preprocessed columns are committed again from their descriptions in the layouts,
+/// so that the commitment of the key can't vouch for other columns. This costs a
+/// low-degree extension of the preprocessed columns.
+pub fn verify_proof(
+    vk: &VerifyingKey,
+    header: &ProofHeader,
+    proof: &Proof<Config>,
+) -> Result<(), VerifyError> {
+    if header.fri_parameters != vk.fri_parameters {
+        return Err(VerifyError::FriParametersMismatch);
+    }
+
+    if proof.degree_bits.len() != vk.tables.len() {
+        return Err(VerifyError::LayoutMismatch(format!(
+            "proof has {} tables, verifying key has {}",
+            proof.degree_bits.len(),
+            vk.tables.len()
+        )));
+    }
+
+    if header.arguments != vk.arguments {
+        return Err(VerifyError::LayoutMismatch(
+            "proof was generated for other arguments".to_string(),
+        ));
+    }
+
+    let mut airs = Vec::new();
+
+    for (i, (table, degree_bits)) in vk.tables.iter().zip(&proof.degree_bits).enumerate() {
+        if *degree_bits >= usize::BITS as usize || 1 << degree_bits != table.height {
+            return Err(VerifyError::LayoutMismatch(format!(
+                "proof height of table {} doesn't match the verifying key height {}",
+                i, table.height
+            )));
+        }
+
+        let air = table.air();
+
+        if <LineaAIR as BaseAir<Val>>::width(&air) != table.width {
+            return Err(VerifyError::LayoutMismatch(format!(
+                "configs of table {} describe {} main columns, verifying key declares {}",
+                i,
+                <LineaAIR as BaseAir<Val>>::width(&air),
+                table.width
+            )));
+        }
+
+        let aux_width = <LineaAIR as MultiStageAir<Val>>::aux_width(&air);
+        if aux_width != table.aux_width {
+            return Err(VerifyError::LayoutMismatch(format!(
+                "configs of table {} describe {} auxiliary columns, verifying key declares {}",
+                i, aux_width, table.aux_width
+            )));
+        }
+
+        let preprocessed_ids = table
+            .configs
+            .iter()
+            .flat_map(|c| c.preprocessed_columns_ids());
+        if preprocessed_ids
+            .max()
+            .is_some_and(|id| id >= table.preprocessed.len())
+        {
+            return Err(VerifyError::LayoutMismatch(format!(
+                "configs of table {} reference more than its {} preprocessed columns",
+                i,
+                table.preprocessed.len()
+            )));
+        }
+
+        airs.push(air);
+    }
+
+    let config = stark_config(&vk.fri_parameters);
+
+    if setup(&config, &airs).map(|p| p.commitment) != vk.preprocessed {
+        return Err(VerifyError::LayoutMismatch(
+            "preprocessed commitment doesn't match the preprocessed columns of the tables"
+                .to_string(),
+        ));
+    }
+
+    let mut challenger = vk.challenger();
+    verify(
+        &config,
+        &airs,
+        vk.preprocessed.as_ref(),
+        &mut challenger,
+        proof,
+        &vec![],
+    )
+    .map_err(VerifyError::Verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FriParameters;
+    use crate::prove::prove_tables;
+    use p3_field::FieldAlgebra;
+    use trace::tables::RawTables;
+    use trace::testing::{lookup, permutation, range};
+
+    /// Proof of a small lookup and a small permutation.
+    fn prove_arguments() -> (ProofHeader, Proof<Config>, VerifyingKey) {
+        let lookup = lookup("lookup", &[3, 1, 3, 2], &[&[1, 2, 3, 4]]);
+        let permutation = permutation("permutation", &[1, 2, 3, 4], &[4, 2, 1, 3]);
+
+        let tables = RawTables::new(vec![permutation], vec![lookup], vec![], 1, false).unwrap();
+        prove_tables(&tables, FriParameters::default()).unwrap()
+    }
+
+    #[test]
+    fn proof_verifies_against_its_key() {
+        let (header, proof, vk) = prove_arguments();
+        verify_proof(&vk, &header, &proof).unwrap();
+    }
+
+    #[test]
+    fn tampered_aux_commitment_is_rejected() {
+        let (header, mut proof, vk) = prove_arguments();
+        proof.commitments.aux = proof.commitments.main.clone();
+
+        assert!(matches!(
+            verify_proof(&vk, &header, &proof),
+            Err(VerifyError::Verification(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_digest_is_rejected() {
+        let (header, proof, mut vk) = prove_arguments();
+        // The digest is observed first, so every challenge differs from the proving ones
+        vk.digest += Val::ONE;
+
+        assert!(matches!(
+            verify_proof(&vk, &header, &proof),
+            Err(VerifyError::Verification(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_aux_width_is_rejected() {
+        let (header, proof, mut vk) = prove_arguments();
+        vk.tables[0].aux_width += 1;
+
+        assert!(matches!(
+            verify_proof(&vk, &header, &proof),
+            Err(VerifyError::LayoutMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_degree_bits_are_rejected() {
+        let (header, mut proof, vk) = prove_arguments();
+        proof.degree_bits[0] += 1;
+
+        assert!(matches!(
+            verify_proof(&vk, &header, &proof),
+            Err(VerifyError::LayoutMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn preprocessed_commitment_must_match_the_tables() {
+        let tables = RawTables::new(
+            vec![],
+            vec![],
+            vec![range("range", &[0, 7, 3, 5], 3)],
+            1,
+            false,
+        )
+        .unwrap();
+        let (header, proof, vk) = prove_tables(&tables, FriParameters::default()).unwrap();
+        verify_proof(&vk, &header, &proof).unwrap();
+
+        // Commitment of a wider range table, under a key with a valid digest
+        let other =
+            RawTables::new(vec![], vec![], vec![range("range", &[0], 4)], 1, false).unwrap();
+        let (_, _, other_vk) = prove_tables(&other, FriParameters::default()).unwrap();
+        let tampered = VerifyingKey::new(
+            vk.fri_parameters.clone(),
+            vk.arguments.clone(),
+            vk.tables.clone(),
+            other_vk.preprocessed.clone(),
+        );
+
+        assert!(matches!(
+            verify_proof(&tampered, &header, &proof),
+            Err(VerifyError::LayoutMismatch(_))
+        ));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::artifact::{read_versioned_header, write_cbor, ArtifactError};
+use crate::config::{hash, new_challenger, Challenger, Config, FriParameters, HashParameters, Val};
+use air::preprocessed::FixedColumn;
+use air::{AirConfig, LineaAIR};
+use ark_ff::PrimeField;
+use p3_bls12_377_fr::FF_Bls12_377Fr;
+use p3_challenger::CanObserve;
+use p3_field::FieldAlgebra;
+use p3_symmetric::CryptographicHasher;
+use prover::Com;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+/// Version of the verifying key layout. Bump on any change of `VerifyingKey`.
+pub const VK_FORMAT_VERSION: u32 = 8;
+
+/// Amount of bytes packed into one field element when hashing the verifying key.
+const DIGEST_CHUNK_BYTES: usize = 31;
+
+/// Layout of one table: a `LineaAIR` over arguments of the same height.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TableLayout {
+    pub configs: Vec<AirConfig>,
+    /// Width of the main trace of the table.
+    pub width: usize,
+    /// Width of the auxiliary trace of the table.
+    pub aux_width: usize,
+    /// Height of the table.
+    pub height: usize,
+    /// Preprocessed columns referenced by the configs, see `VerifyingKey::preprocessed`.
+    pub preprocessed: Vec<FixedColumn>,
+}
+
+impl TableLayout {
+    pub fn new(configs: Vec<AirConfig>, preprocessed: Vec<FixedColumn>, height: usize) -> Self {
+        TableLayout {
+            width: air::main_trace_width(&configs),
+            aux_width: configs.iter().map(|c| c.aux_width()).sum(),
+            configs,
+            height,
+            preprocessed,
+        }
+    }
+
+    pub fn air(&self) -> LineaAIR {
+        LineaAIR::with_preprocessed(self.configs.clone(), self.preprocessed.clone(), self.height)
+    }
+}
+
+/// Everything the verifier needs besides the proof: the `LineaAIR` layout of every table
+/// and the parameters of the PCS and the hash.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VerifyingKey {
+    pub version: u32,
+    pub fri_parameters: FriParameters,
+    pub hash_parameters: HashParameters,
+    /// Names of the arguments in the order of the table configs.
+    pub arguments: Vec<String>,
+    pub tables: Vec<TableLayout>,
+    /// Commitment of the preprocessed columns of all tables, computed once at setup. `None`
+    /// if no table has any.
+    pub preprocessed: Option<Com<Config>>,
+    /// Poseidon2 digest of all the fields above.
+    pub digest: Val,
+}
+
+impl VerifyingKey {
+    pub fn new(
+        fri_parameters: FriParameters,
+        arguments: Vec<String>,
+        tables: Vec<TableLayout>,
+        preprocessed: Option<Com<Config>>,
+    ) -> Self {
+        let mut vk = VerifyingKey {
+            version: VK_FORMAT_VERSION,
+            fri_parameters,
+            hash_parameters: HashParameters::default(),
+            arguments,
+            tables,
+            preprocessed,
+            digest: Val::ZERO,
+        };
+        vk.digest = vk.compute_digest();
+        vk
+    }
+
+    /// Hashes the CBOR encoding of the key (without the digest itself), packed into field
+    /// elements of 31 bytes and prefixed with its length.
+    pub fn compute_digest(&self) -> Val {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(
+            &(
+                self.version,
+                &self.fri_parameters,
+                &self.hash_parameters,
+                &self.arguments,
+                &self.tables,
+                &self.preprocessed,
+            ),
+            &mut bytes,
+        )
+        .expect("verifying key is always encodable");
+
+        let elements = std::iter::once(Val::from_canonical_usize(bytes.len())).chain(
+            bytes
+                .chunks(DIGEST_CHUNK_BYTES)
+                .map(|chunk| Val::new(FF_Bls12_377Fr::from_le_bytes_mod_order(chunk))),
+        );
+
+        hash().hash_iter(elements)[0]
+    }
+
+    /// Challenger with the digest already observed, so the proof is bound to this key.
+    pub fn challenger(&self) -> Challenger {
+        let mut challenger = new_challenger();
+        challenger.observe(self.digest);
+        challenger
+    }
+}
+
+pub fn write_verifying_key(path: &str, vk: &VerifyingKey) -> Result<(), ArtifactError> {
+    let file = File::create(path).map_err(|e| ArtifactError::Io(path.to_string(), e))?;
+    write_cbor(vk, &mut BufWriter::new(file))
+}
+
+pub fn read_verifying_key(path: &str) -> Result<VerifyingKey, ArtifactError> {
+    let file = File::open(path).map_err(|e| ArtifactError::Io(path.to_string(), e))?;
+    let vk: VerifyingKey = read_versioned_header(&mut BufReader::new(file), VK_FORMAT_VERSION)?;
+
+    if vk.hash_parameters != HashParameters::default() {
+        return Err(ArtifactError::HashParametersMismatch);
+    }
+
+    if vk.digest != vk.compute_digest() {
+        return Err(ArtifactError::Decode(
+            "verifying key digest doesn't match its content".to_string(),
+        ));
+    }
+
+    Ok(vk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_key() -> VerifyingKey {
+        VerifyingKey::new(
+            FriParameters::default(),
+            vec!["argument".to_string()],
+            vec![TableLayout::new(vec![], vec![], 4)],
+            None,
+        )
+    }
+
+    /// Writes the key to a temporary file and reads it back.
+    fn write_and_read(name: &str, vk: &VerifyingKey) -> Result<VerifyingKey, ArtifactError> {
+        let path = std::env::temp_dir().join(format!("{}_{}.bin", name, std::process::id()));
+        let path = path.to_str().unwrap();
+        write_verifying_key(path, vk).unwrap();
+        let result = read_verifying_key(path);
+        std::fs::remove_file(path).unwrap();
+        result
+    }
+
+    #[test]
+    fn tampered_digest_is_rejected() {
+        let mut vk = small_key();
+        vk.digest += Val::ONE;
+
+        assert!(matches!(
+            write_and_read("vk_digest", &vk),
+            Err(ArtifactError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn tampered_content_is_rejected() {
+        let mut vk = small_key();
+        vk.arguments[0] = "other".to_string();
+
+        assert!(matches!(
+            write_and_read("vk_content", &vk),
+            Err(ArtifactError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn key_survives_a_round_trip() {
+        let vk = small_key();
+        let read = write_and_read("vk_round_trip", &vk).unwrap();
+
+        assert_eq!(format!("{:?}", read), format!("{:?}", vk));
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let mut vk = small_key();
+        vk.version = VK_FORMAT_VERSION + 1;
+        vk.digest = vk.compute_digest();
+
+        assert!(matches!(
+            write_and_read("vk_version", &vk),
+            Err(ArtifactError::VersionMismatch { found, .. }) if found == VK_FORMAT_VERSION + 1
+        ));
+    }
+}
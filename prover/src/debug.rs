@@ -0,0 +1,170 @@
+use crate::ViewPair;
+use air::{ConstraintKind, MultiStageAirBuilder};
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, PairBuilder};
+use p3_field::{Field, FieldAlgebra};
+use p3_matrix::dense::{RowMajorMatrix, RowMajorMatrixView};
+use p3_matrix::stack::VerticalPair;
+use p3_matrix::Matrix;
+use p3_maybe_rayon::prelude::*;
+use std::fmt::{Display, Formatter};
+use tracing::instrument;
+
+/// Constraint that doesn't hold on a row of the trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConstraintFailure {
+    pub row: usize,
+    /// Index of the argument config in the AIR.
+    pub argument: usize,
+    pub kind: ConstraintKind,
+}
+
+impl Display for ConstraintFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} constraint fails at row {}", self.kind, self.row)
+    }
+}
+
+/// Evaluates the constraints of a single row pair and records the failing ones, instead of
+/// folding them like the prover does.
+pub struct DebugConstraintBuilder<'a, F: Field> {
+    pub row: usize,
+    pub main: ViewPair<'a, F>,
+    pub aux: ViewPair<'a, F>,
+    pub preprocessed: ViewPair<'a, F>,
+    pub public_values: &'a [F],
+    pub challenges: &'a [F],
+    pub is_first_row: F,
+    pub is_last_row: F,
+    pub is_transition: F,
+    /// Argument and kind of the constraints being asserted, see `annotate`.
+    pub current: (usize, ConstraintKind),
+    pub failures: Vec<ConstraintFailure>,
+}
+
+impl<'a, F: Field> AirBuilder for DebugConstraintBuilder<'a, F> {
+    type F = F;
+    type Expr = F;
+    type Var = F;
+    type M = ViewPair<'a, F>;
+
+    fn main(&self) -> Self::M {
+        self.main
+    }
+
+    fn is_first_row(&self) -> Self::Expr {
+        self.is_first_row
+    }
+
+    fn is_last_row(&self) -> Self::Expr {
+        self.is_last_row
+    }
+
+    fn is_transition_window(&self, size: usize) -> Self::Expr {
+        if size == 2 {
+            self.is_transition
+        } else {
+            panic!("only a window size of 2 is supported")
+        }
+    }
+
+    fn assert_zero<I: Into<Self::Expr>>(&mut self, x: I) {
+        if !x.into().is_zero() {
+            let (argument, kind) = self.current;
+            self.failures.push(ConstraintFailure {
+                row: self.row,
+                argument,
+                kind,
+            });
+        }
+    }
+}
+
+impl<F: Field> AirBuilderWithPublicValues for DebugConstraintBuilder<'_, F> {
+    type PublicVar = F;
+
+    fn public_values(&self) -> &[Self::PublicVar] {
+        self.public_values
+    }
+}
+
+impl<F: Field> PairBuilder for DebugConstraintBuilder<'_, F> {
+    fn preprocessed(&self) -> Self::M {
+        self.preprocessed
+    }
+}
+
+impl<F: Field> MultiStageAirBuilder for DebugConstraintBuilder<'_, F> {
+    fn aux(&self) -> Self::M {
+        self.aux
+    }
+
+    fn challenges(&self) -> &[Self::PublicVar] {
+        self.challenges
+    }
+
+    fn annotate(&mut self, argument: usize, kind: ConstraintKind) {
+        self.current = (argument, kind);
+    }
+}
+
+/// Evaluates the constraints of `air` on every row and the next one of the given traces,
+/// the last row is paired with the first one. Returns all failures in the row order.
+/// `preprocessed` has to be given if the AIR has preprocessed columns.
+///
+/// Unlike proving, nothing is committed or extended, so it quickly points at the row and
+/// the constraint that make a proof fail.
+#[instrument(skip_all)]
+pub fn check_constraints<F, A>(
+    air: &A,
+    main: &RowMajorMatrix<F>,
+    aux: &RowMajorMatrix<F>,
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    public_values: &[F],
+    challenges: &[F],
+) -> Vec<ConstraintFailure>
+where
+    F: Field,
+    A: for<'a> Air<DebugConstraintBuilder<'a, F>> + Sync,
+{
+    let height = main.height();
+    let no_preprocessed = RowMajorMatrix::new(vec![], 0);
+    let preprocessed = preprocessed.unwrap_or(&no_preprocessed);
+
+    let failures: Vec<Vec<ConstraintFailure>> = (0..height)
+        .into_par_iter()
+        .map(|i| {
+            let next = (i + 1) % height;
+            let mut builder = DebugConstraintBuilder {
+                row: i,
+                main: VerticalPair::new(
+                    RowMajorMatrixView::new_row(row(main, i)),
+                    RowMajorMatrixView::new_row(row(main, next)),
+                ),
+                aux: VerticalPair::new(
+                    RowMajorMatrixView::new_row(row(aux, i)),
+                    RowMajorMatrixView::new_row(row(aux, next)),
+                ),
+                preprocessed: VerticalPair::new(
+                    RowMajorMatrixView::new_row(row(preprocessed, i)),
+                    RowMajorMatrixView::new_row(row(preprocessed, next)),
+                ),
+                public_values,
+                challenges,
+                is_first_row: F::from_bool(i == 0),
+                is_last_row: F::from_bool(i == height - 1),
+                is_transition: F::from_bool(i != height - 1),
+                current: (0, ConstraintKind::Inverse),
+                failures: Vec::new(),
+            };
+            air.eval(&mut builder);
+            builder.failures
+        })
+        .collect();
+
+    failures.into_iter().flatten().collect()
+}
+
+fn row<F: Clone + Send + Sync>(matrix: &RowMajorMatrix<F>, i: usize) -> &[F] {
+    let width = matrix.width();
+    &matrix.values[i * width..(i + 1) * width]
+}
@@ -0,0 +1,151 @@
+use air::MultiStageAirBuilder;
+use p3_air::{AirBuilder, AirBuilderWithPublicValues, PairBuilder};
+use p3_matrix::dense::RowMajorMatrixView;
+use p3_matrix::stack::VerticalPair;
+use p3_uni_stark::{StarkGenericConfig, Val};
+
+pub type ViewPair<'a, T> = VerticalPair<RowMajorMatrixView<'a, T>, RowMajorMatrixView<'a, T>>;
+
+/// Folds all constraints of a row pair of the quotient domain into one value.
+pub struct ProverConstraintFolder<'a, SC: StarkGenericConfig> {
+    pub main: ViewPair<'a, Val<SC>>,
+    pub aux: ViewPair<'a, Val<SC>>,
+    /// Preprocessed columns, zero width if the table has none.
+    pub preprocessed: ViewPair<'a, Val<SC>>,
+    pub public_values: &'a [Val<SC>],
+    pub challenges: &'a [Val<SC>],
+    pub is_first_row: Val<SC>,
+    pub is_last_row: Val<SC>,
+    pub is_transition: Val<SC>,
+    pub alpha: SC::Challenge,
+    pub accumulator: SC::Challenge,
+}
+
+/// Folds all constraints over the values opened at `zeta` and `zeta_next` into one value.
+pub struct VerifierConstraintFolder<'a, SC: StarkGenericConfig> {
+    pub main: ViewPair<'a, SC::Challenge>,
+    pub aux: ViewPair<'a, SC::Challenge>,
+    /// Preprocessed columns, zero width if the table has none.
+    pub preprocessed: ViewPair<'a, SC::Challenge>,
+    pub public_values: &'a [Val<SC>],
+    pub challenges: &'a [Val<SC>],
+    pub is_first_row: SC::Challenge,
+    pub is_last_row: SC::Challenge,
+    pub is_transition: SC::Challenge,
+    pub alpha: SC::Challenge,
+    pub accumulator: SC::Challenge,
+}
+
+impl<'a, SC: StarkGenericConfig> AirBuilder for ProverConstraintFolder<'a, SC> {
+    type F = Val<SC>;
+    type Expr = Val<SC>;
+    type Var = Val<SC>;
+    type M = ViewPair<'a, Val<SC>>;
+
+    fn main(&self) -> Self::M {
+        self.main
+    }
+
+    fn is_first_row(&self) -> Self::Expr {
+        self.is_first_row
+    }
+
+    fn is_last_row(&self) -> Self::Expr {
+        self.is_last_row
+    }
+
+    fn is_transition_window(&self, size: usize) -> Self::Expr {
+        if size == 2 {
+            self.is_transition
+        } else {
+            panic!("only a window size of 2 is supported")
+        }
+    }
+
+    fn assert_zero<I: Into<Self::Expr>>(&mut self, x: I) {
+        let x: Val<SC> = x.into();
+        self.accumulator *= self.alpha;
+        self.accumulator += x;
+    }
+}
+
+impl<SC: StarkGenericConfig> AirBuilderWithPublicValues for ProverConstraintFolder<'_, SC> {
+    type PublicVar = Val<SC>;
+
+    fn public_values(&self) -> &[Self::PublicVar] {
+        self.public_values
+    }
+}
+
+impl<SC: StarkGenericConfig> PairBuilder for ProverConstraintFolder<'_, SC> {
+    fn preprocessed(&self) -> Self::M {
+        self.preprocessed
+    }
+}
+
+impl<SC: StarkGenericConfig> MultiStageAirBuilder for ProverConstraintFolder<'_, SC> {
+    fn aux(&self) -> Self::M {
+        self.aux
+    }
+
+    fn challenges(&self) -> &[Self::PublicVar] {
+        self.challenges
+    }
+}
+
+impl<'a, SC: StarkGenericConfig> AirBuilder for VerifierConstraintFolder<'a, SC> {
+    type F = Val<SC>;
+    type Expr = SC::Challenge;
+    type Var = SC::Challenge;
+    type M = ViewPair<'a, SC::Challenge>;
+
+    fn main(&self) -> Self::M {
+        self.main
+    }
+
+    fn is_first_row(&self) -> Self::Expr {
+        self.is_first_row
+    }
+
+    fn is_last_row(&self) -> Self::Expr {
+        self.is_last_row
+    }
+
+    fn is_transition_window(&self, size: usize) -> Self::Expr {
+        if size == 2 {
+            self.is_transition
+        } else {
+            panic!("only a window size of 2 is supported")
+        }
+    }
+
+    fn assert_zero<I: Into<Self::Expr>>(&mut self, x: I) {
+        let x: SC::Challenge = x.into();
+        self.accumulator *= self.alpha;
+        self.accumulator += x;
+    }
+}
+
+impl<SC: StarkGenericConfig> AirBuilderWithPublicValues for VerifierConstraintFolder<'_, SC> {
+    type PublicVar = Val<SC>;
+
+    fn public_values(&self) -> &[Self::PublicVar] {
+        self.public_values
+    }
+}
+
+impl<SC: StarkGenericConfig> PairBuilder for VerifierConstraintFolder<'_, SC> {
+    fn preprocessed(&self) -> Self::M {
+        self.preprocessed
+    }
+}
+
+impl<SC: StarkGenericConfig> MultiStageAirBuilder for VerifierConstraintFolder<'_, SC> {
+    fn aux(&self) -> Self::M {
+        self.aux
+    }
+
+    fn challenges(&self) -> &[Self::PublicVar] {
+        self.challenges
+    }
+}
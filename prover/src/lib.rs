@@ -0,0 +1,29 @@
+//! Two-stage STARK prover for `LineaAIR`.
+//!
+//! Unlike `p3_uni_stark`, the trace is split in two parts: the main trace (raw lookup and
+//! permutation columns) is committed first, then the lookup/permutation challenges are
+//! sampled from the transcript, and only after that the auxiliary trace (inverses,
+//! multiplicities and check columns) is generated and committed.
+//!
+//! The proof covers several tables, one AIR each, so that arguments of different heights
+//! don't have to be padded to the largest one. Tables share the challenges and every
+//! commitment, and are opened in one batch.
+//!
+//! Preprocessed columns (e.g. range tables) are committed once by `setup`, the commitment
+//! goes to the verifying key and is opened along with the traces.
+
+mod debug;
+mod folder;
+mod proof;
+mod prover;
+mod setup;
+mod symbolic;
+mod verifier;
+
+pub use debug::*;
+pub use folder::*;
+pub use proof::*;
+pub use prover::*;
+pub use setup::*;
+pub use symbolic::*;
+pub use verifier::*;
@@ -0,0 +1,50 @@
+use p3_commit::Pcs;
+use p3_uni_stark::StarkGenericConfig;
+use serde::{Deserialize, Serialize};
+
+pub type Com<SC> = <<SC as StarkGenericConfig>::Pcs as Pcs<
+    <SC as StarkGenericConfig>::Challenge,
+    <SC as StarkGenericConfig>::Challenger,
+>>::Commitment;
+
+pub type PcsProverData<SC> = <<SC as StarkGenericConfig>::Pcs as Pcs<
+    <SC as StarkGenericConfig>::Challenge,
+    <SC as StarkGenericConfig>::Challenger,
+>>::ProverData;
+
+pub type PcsProof<SC> = <<SC as StarkGenericConfig>::Pcs as Pcs<
+    <SC as StarkGenericConfig>::Challenge,
+    <SC as StarkGenericConfig>::Challenger,
+>>::Proof;
+
+/// Proof of several tables of possibly different heights. Every commitment covers the
+/// matrices of all tables, so there is a single batched opening.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Proof<SC: StarkGenericConfig> {
+    pub commitments: Commitments<Com<SC>>,
+    /// Opened values per table.
+    pub opened_values: Vec<OpenedValues<SC::Challenge>>,
+    pub opening_proof: PcsProof<SC>,
+    /// Log2 of the height per table.
+    pub degree_bits: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Commitments<Com> {
+    pub main: Com,
+    pub aux: Com,
+    pub quotient_chunks: Com,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenedValues<Challenge> {
+    /// Empty if the table has no preprocessed columns.
+    pub preprocessed_local: Vec<Challenge>,
+    pub preprocessed_next: Vec<Challenge>,
+    pub main_local: Vec<Challenge>,
+    pub main_next: Vec<Challenge>,
+    pub aux_local: Vec<Challenge>,
+    pub aux_next: Vec<Challenge>,
+    pub quotient_chunks: Vec<Vec<Challenge>>,
+}
@@ -0,0 +1,316 @@
+use crate::{
+    get_log_quotient_degree, Commitments, MultiStageSymbolicBuilder, OpenedValues,
+    PreprocessedData, Proof, ProverConstraintFolder,
+};
+use air::MultiStageAir;
+use p3_air::Air;
+use p3_challenger::{CanObserve, CanSample, FieldChallenger};
+use p3_commit::{Pcs, PolynomialSpace};
+use p3_field::FieldAlgebra;
+use p3_matrix::dense::{RowMajorMatrix, RowMajorMatrixView};
+use p3_matrix::stack::VerticalPair;
+use p3_matrix::Matrix;
+use p3_maybe_rayon::prelude::*;
+use p3_uni_stark::{Domain, StarkGenericConfig, Val};
+use p3_util::log2_strict_usize;
+use tracing::{info_span, instrument};
+
+/// Proves every table `airs[i]` over `main_traces[i]` and the auxiliary traces built by
+/// `aux_traces`, one per table. Tables may have different heights.
+///
+/// `aux_traces` is called with the challenges sampled after the main trace commitment
+/// has been observed by the challenger, so the prover can't choose the main traces
+/// depending on them. Its error (e.g. an unsatisfied argument) is returned as is.
+///
+/// `preprocessed` is the `setup` output of the same `airs`, required if any of them has
+/// preprocessed columns.
+#[instrument(skip_all)]
+pub fn prove<SC, A, F, E>(
+    config: &SC,
+    airs: &[A],
+    preprocessed: Option<&PreprocessedData<SC>>,
+    challenger: &mut SC::Challenger,
+    main_traces: Vec<RowMajorMatrix<Val<SC>>>,
+    aux_traces: F,
+    public_values: &Vec<Val<SC>>,
+) -> Result<Proof<SC>, E>
+where
+    SC: StarkGenericConfig,
+    A: MultiStageAir<Val<SC>>
+        + Air<MultiStageSymbolicBuilder<Val<SC>>>
+        + for<'a> Air<ProverConstraintFolder<'a, SC>>,
+    F: FnOnce(&[Val<SC>]) -> Result<Vec<RowMajorMatrix<Val<SC>>>, E>,
+{
+    assert_eq!(
+        main_traces.len(),
+        airs.len(),
+        "one main trace per table expected"
+    );
+
+    let pcs = config.pcs();
+
+    // Matrix of each table within the preprocessed commitment
+    let preprocessed_indices: Vec<Option<usize>> = airs
+        .iter()
+        .enumerate()
+        .map(|(i, air)| {
+            let index = preprocessed.and_then(|p| p.indices.get(i).copied().flatten());
+            assert_eq!(
+                index.is_some(),
+                air.preprocessed_width() > 0,
+                "preprocessed data doesn't match the tables"
+            );
+            index
+        })
+        .collect();
+
+    let degree_bits: Vec<usize> = main_traces
+        .iter()
+        .map(|trace| log2_strict_usize(trace.height()))
+        .collect();
+    let trace_domains: Vec<Domain<SC>> = main_traces
+        .iter()
+        .map(|trace| pcs.natural_domain_for_degree(trace.height()))
+        .collect();
+    let log_quotient_degrees: Vec<usize> = airs
+        .iter()
+        .map(|air| get_log_quotient_degree::<Val<SC>, A>(air, public_values.len()))
+        .collect();
+
+    if let Some(preprocessed) = preprocessed {
+        for (index, trace_domain) in preprocessed_indices.iter().zip(&trace_domains) {
+            if let Some(index) = index {
+                assert_eq!(
+                    preprocessed.domains[*index].size(),
+                    trace_domain.size(),
+                    "preprocessed trace height mismatch"
+                );
+            }
+        }
+    }
+
+    let (main_commit, main_data) = info_span!("commit to main trace data")
+        .in_scope(|| pcs.commit(trace_domains.iter().copied().zip(main_traces).collect()));
+
+    // Observe the instance.
+    if let Some(preprocessed) = preprocessed {
+        challenger.observe(preprocessed.commitment.clone());
+    }
+    for log_degree in &degree_bits {
+        challenger.observe(Val::<SC>::from_canonical_usize(*log_degree));
+    }
+    challenger.observe(main_commit.clone());
+    challenger.observe_slice(public_values);
+
+    // Lookup and permutation challenges are sampled only after the main traces are fixed.
+    // They are shared by all tables.
+    let challenges: Vec<Val<SC>> = (0..num_challenges::<Val<SC>, A>(airs))
+        .map(|_| challenger.sample())
+        .collect();
+
+    let aux_traces = info_span!("generate auxiliary trace").in_scope(|| aux_traces(&challenges))?;
+    assert_eq!(
+        aux_traces.len(),
+        airs.len(),
+        "one auxiliary trace per table expected"
+    );
+    for (aux_trace, trace_domain) in aux_traces.iter().zip(&trace_domains) {
+        assert_eq!(
+            aux_trace.height(),
+            trace_domain.size(),
+            "auxiliary trace height mismatch"
+        );
+    }
+
+    let (aux_commit, aux_data) = info_span!("commit to auxiliary trace data")
+        .in_scope(|| pcs.commit(trace_domains.iter().copied().zip(aux_traces).collect()));
+    challenger.observe(aux_commit.clone());
+
+    let alpha: SC::Challenge = challenger.sample_ext_element();
+
+    // Quotient chunks of all tables, table by table.
+    let mut quotient_chunks = Vec::new();
+
+    for (i, air) in airs.iter().enumerate() {
+        let quotient_degree = 1 << log_quotient_degrees[i];
+        let quotient_domain = trace_domains[i]
+            .create_disjoint_domain(1 << (degree_bits[i] + log_quotient_degrees[i]));
+
+        let main_on_quotient_domain = pcs.get_evaluations_on_domain(&main_data, i, quotient_domain);
+        let aux_on_quotient_domain = pcs.get_evaluations_on_domain(&aux_data, i, quotient_domain);
+        let preprocessed_on_quotient_domain =
+            preprocessed
+                .zip(preprocessed_indices[i])
+                .map(|(preprocessed, index)| {
+                    pcs.get_evaluations_on_domain(&preprocessed.data, index, quotient_domain)
+                        .to_row_major_matrix()
+                });
+
+        let quotient_values = info_span!("compute quotient polynomial", table = i).in_scope(|| {
+            quotient_values::<SC, A, _, _>(
+                air,
+                public_values,
+                &challenges,
+                trace_domains[i],
+                quotient_domain,
+                main_on_quotient_domain,
+                aux_on_quotient_domain,
+                preprocessed_on_quotient_domain,
+                alpha,
+            )
+        });
+        let quotient_flat = RowMajorMatrix::new_col(quotient_values).flatten_to_base();
+        let chunks = quotient_domain.split_evals(quotient_degree, quotient_flat);
+        let qc_domains = quotient_domain.split_domains(quotient_degree);
+
+        quotient_chunks.extend(qc_domains.into_iter().zip(chunks));
+    }
+
+    let num_quotient_chunks = quotient_chunks.len();
+
+    let (quotient_commit, quotient_data) =
+        info_span!("commit to quotient poly chunks").in_scope(|| pcs.commit(quotient_chunks));
+    challenger.observe(quotient_commit.clone());
+
+    let commitments = Commitments {
+        main: main_commit,
+        aux: aux_commit,
+        quotient_chunks: quotient_commit,
+    };
+
+    let zeta: SC::Challenge = challenger.sample_ext_element();
+
+    // Every table is opened at zeta and at the next point of its own domain
+    let trace_points: Vec<Vec<SC::Challenge>> = trace_domains
+        .iter()
+        .map(|trace_domain| vec![zeta, trace_domain.next_point(zeta).unwrap()])
+        .collect();
+
+    let mut rounds = vec![
+        (&main_data, trace_points.clone()),
+        (&aux_data, trace_points.clone()),
+        (
+            &quotient_data,
+            // open every chunk at zeta
+            (0..num_quotient_chunks).map(|_| vec![zeta]).collect(),
+        ),
+    ];
+    if let Some(preprocessed) = preprocessed {
+        let preprocessed_points = preprocessed_indices
+            .iter()
+            .zip(&trace_points)
+            .filter(|(index, _)| index.is_some())
+            .map(|(_, points)| points.clone())
+            .collect();
+        rounds.push((&preprocessed.data, preprocessed_points));
+    }
+
+    let (openings, opening_proof) = info_span!("open").in_scope(|| pcs.open(rounds, challenger));
+
+    let mut quotient_openings = openings[2].iter();
+
+    let opened_values = log_quotient_degrees
+        .iter()
+        .enumerate()
+        .map(|(i, &log_quotient_degree)| OpenedValues {
+            preprocessed_local: preprocessed_indices[i]
+                .map_or(vec![], |index| openings[3][index][0].clone()),
+            preprocessed_next: preprocessed_indices[i]
+                .map_or(vec![], |index| openings[3][index][1].clone()),
+            main_local: openings[0][i][0].clone(),
+            main_next: openings[0][i][1].clone(),
+            aux_local: openings[1][i][0].clone(),
+            aux_next: openings[1][i][1].clone(),
+            quotient_chunks: quotient_openings
+                .by_ref()
+                .take(1 << log_quotient_degree)
+                .map(|v| v[0].clone())
+                .collect(),
+        })
+        .collect();
+
+    Ok(Proof {
+        commitments,
+        opened_values,
+        opening_proof,
+        degree_bits,
+    })
+}
+
+/// Amount of challenges shared by all tables.
+pub(crate) fn num_challenges<F, A: MultiStageAir<F>>(airs: &[A]) -> usize {
+    airs.iter()
+        .map(|air| air.num_challenges())
+        .max()
+        .unwrap_or(0)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn quotient_values<SC, A, MainMat, AuxMat>(
+    air: &A,
+    public_values: &[Val<SC>],
+    challenges: &[Val<SC>],
+    trace_domain: Domain<SC>,
+    quotient_domain: Domain<SC>,
+    main_on_quotient_domain: MainMat,
+    aux_on_quotient_domain: AuxMat,
+    preprocessed_on_quotient_domain: Option<RowMajorMatrix<Val<SC>>>,
+    alpha: SC::Challenge,
+) -> Vec<SC::Challenge>
+where
+    SC: StarkGenericConfig,
+    A: for<'a> Air<ProverConstraintFolder<'a, SC>>,
+    MainMat: Matrix<Val<SC>>,
+    AuxMat: Matrix<Val<SC>>,
+{
+    let quotient_size = quotient_domain.size();
+    let sels = trace_domain.selectors_on_coset(quotient_domain);
+
+    let qdb = log2_strict_usize(quotient_size) - log2_strict_usize(trace_domain.size());
+    let next_step = 1 << qdb;
+
+    (0..quotient_size)
+        .into_par_iter()
+        .map(|i| {
+            let i_next = (i + next_step) % quotient_size;
+
+            let main_local = main_on_quotient_domain.row_slice(i).to_vec();
+            let main_next = main_on_quotient_domain.row_slice(i_next).to_vec();
+            let aux_local = aux_on_quotient_domain.row_slice(i).to_vec();
+            let aux_next = aux_on_quotient_domain.row_slice(i_next).to_vec();
+            let (preprocessed_local, preprocessed_next) = match &preprocessed_on_quotient_domain {
+                Some(preprocessed) => (
+                    preprocessed.row_slice(i).to_vec(),
+                    preprocessed.row_slice(i_next).to_vec(),
+                ),
+                None => (vec![], vec![]),
+            };
+
+            let mut folder = ProverConstraintFolder {
+                main: VerticalPair::new(
+                    RowMajorMatrixView::new_row(&main_local),
+                    RowMajorMatrixView::new_row(&main_next),
+                ),
+                aux: VerticalPair::new(
+                    RowMajorMatrixView::new_row(&aux_local),
+                    RowMajorMatrixView::new_row(&aux_next),
+                ),
+                preprocessed: VerticalPair::new(
+                    RowMajorMatrixView::new_row(&preprocessed_local),
+                    RowMajorMatrixView::new_row(&preprocessed_next),
+                ),
+                public_values,
+                challenges,
+                is_first_row: sels.is_first_row[i],
+                is_last_row: sels.is_last_row[i],
+                is_transition: sels.is_transition[i],
+                alpha,
+                accumulator: SC::Challenge::ZERO,
+            };
+            air.eval(&mut folder);
+
+            // quotient(x) = constraints(x) / Z_H(x)
+            folder.accumulator * sels.inv_zeroifier[i]
+        })
+        .collect()
+}
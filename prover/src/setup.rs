@@ -0,0 +1,56 @@
+use crate::{Com, PcsProverData};
+use p3_air::BaseAir;
+use p3_commit::Pcs;
+use p3_matrix::Matrix;
+use p3_uni_stark::{Domain, StarkGenericConfig, Val};
+use tracing::{info_span, instrument};
+
+/// Committed preprocessed columns of all tables. The commitment is a part of the verifying
+/// key, the prover data is needed to open it.
+pub struct PreprocessedData<SC: StarkGenericConfig> {
+    pub commitment: Com<SC>,
+    pub data: PcsProverData<SC>,
+    /// Matrix index of each table within the commitment, `None` if the table has no
+    /// preprocessed columns.
+    pub indices: Vec<Option<usize>>,
+    /// Domain of each committed matrix.
+    pub domains: Vec<Domain<SC>>,
+}
+
+/// Commits the preprocessed traces of `airs`, one matrix per table that has any. Returns
+/// `None` if no table has preprocessed columns.
+#[instrument(skip_all)]
+pub fn setup<SC, A>(config: &SC, airs: &[A]) -> Option<PreprocessedData<SC>>
+where
+    SC: StarkGenericConfig,
+    A: BaseAir<Val<SC>>,
+{
+    let pcs = config.pcs();
+
+    let mut indices = Vec::with_capacity(airs.len());
+    let mut matrices = Vec::new();
+    for air in airs {
+        match air.preprocessed_trace() {
+            Some(trace) => {
+                indices.push(Some(matrices.len()));
+                matrices.push((pcs.natural_domain_for_degree(trace.height()), trace));
+            }
+            None => indices.push(None),
+        }
+    }
+
+    if matrices.is_empty() {
+        return None;
+    }
+
+    let domains = matrices.iter().map(|(domain, _)| *domain).collect();
+    let (commitment, data) =
+        info_span!("commit to preprocessed trace data").in_scope(|| pcs.commit(matrices));
+
+    Some(PreprocessedData {
+        commitment,
+        data,
+        indices,
+        domains,
+    })
+}
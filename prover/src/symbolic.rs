@@ -0,0 +1,155 @@
+use air::{MultiStageAir, MultiStageAirBuilder};
+use p3_air::{Air, AirBuilder, AirBuilderWithPublicValues, PairBuilder};
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_uni_stark::{Entry, SymbolicExpression, SymbolicVariable};
+use p3_util::log2_ceil_usize;
+use std::cmp::max;
+
+/// Builder that records constraints symbolically to find the maximal constraint degree.
+/// The auxiliary trace is registered as `Entry::Permutation`, so it has the same degree
+/// as the main trace, while challenges are constants.
+pub struct MultiStageSymbolicBuilder<F: Field> {
+    preprocessed: RowMajorMatrix<SymbolicVariable<F>>,
+    main: RowMajorMatrix<SymbolicVariable<F>>,
+    aux: RowMajorMatrix<SymbolicVariable<F>>,
+    public_values: Vec<SymbolicVariable<F>>,
+    challenges: Vec<SymbolicVariable<F>>,
+    constraints: Vec<SymbolicExpression<F>>,
+}
+
+impl<F: Field> MultiStageSymbolicBuilder<F> {
+    pub fn new(
+        preprocessed_width: usize,
+        width: usize,
+        aux_width: usize,
+        num_public_values: usize,
+        num_challenges: usize,
+    ) -> Self {
+        let preprocessed_values = [0, 1]
+            .into_iter()
+            .flat_map(|offset| {
+                (0..preprocessed_width)
+                    .map(move |index| SymbolicVariable::new(Entry::Preprocessed { offset }, index))
+            })
+            .collect();
+
+        let main_values = [0, 1]
+            .into_iter()
+            .flat_map(|offset| {
+                (0..width).map(move |index| SymbolicVariable::new(Entry::Main { offset }, index))
+            })
+            .collect();
+
+        let aux_values = [0, 1]
+            .into_iter()
+            .flat_map(|offset| {
+                (0..aux_width)
+                    .map(move |index| SymbolicVariable::new(Entry::Permutation { offset }, index))
+            })
+            .collect();
+
+        let public_values = (0..num_public_values)
+            .map(|index| SymbolicVariable::new(Entry::Public, index))
+            .collect();
+
+        let challenges = (0..num_challenges)
+            .map(|index| SymbolicVariable::new(Entry::Challenge, index))
+            .collect();
+
+        Self {
+            preprocessed: RowMajorMatrix::new(preprocessed_values, preprocessed_width),
+            main: RowMajorMatrix::new(main_values, width),
+            aux: RowMajorMatrix::new(aux_values, aux_width),
+            public_values,
+            challenges,
+            constraints: vec![],
+        }
+    }
+
+    pub fn constraints(self) -> Vec<SymbolicExpression<F>> {
+        self.constraints
+    }
+}
+
+impl<F: Field> AirBuilder for MultiStageSymbolicBuilder<F> {
+    type F = F;
+    type Expr = SymbolicExpression<F>;
+    type Var = SymbolicVariable<F>;
+    type M = RowMajorMatrix<Self::Var>;
+
+    fn main(&self) -> Self::M {
+        self.main.clone()
+    }
+
+    fn is_first_row(&self) -> Self::Expr {
+        SymbolicExpression::IsFirstRow
+    }
+
+    fn is_last_row(&self) -> Self::Expr {
+        SymbolicExpression::IsLastRow
+    }
+
+    fn is_transition_window(&self, size: usize) -> Self::Expr {
+        if size == 2 {
+            SymbolicExpression::IsTransition
+        } else {
+            panic!("only a window size of 2 is supported")
+        }
+    }
+
+    fn assert_zero<I: Into<Self::Expr>>(&mut self, x: I) {
+        self.constraints.push(x.into());
+    }
+}
+
+impl<F: Field> AirBuilderWithPublicValues for MultiStageSymbolicBuilder<F> {
+    type PublicVar = SymbolicVariable<F>;
+
+    fn public_values(&self) -> &[Self::PublicVar] {
+        &self.public_values
+    }
+}
+
+impl<F: Field> PairBuilder for MultiStageSymbolicBuilder<F> {
+    fn preprocessed(&self) -> Self::M {
+        self.preprocessed.clone()
+    }
+}
+
+impl<F: Field> MultiStageAirBuilder for MultiStageSymbolicBuilder<F> {
+    fn aux(&self) -> Self::M {
+        self.aux.clone()
+    }
+
+    fn challenges(&self) -> &[Self::PublicVar] {
+        &self.challenges
+    }
+}
+
+pub fn get_log_quotient_degree<F, A>(air: &A, num_public_values: usize) -> usize
+where
+    F: Field,
+    A: MultiStageAir<F> + Air<MultiStageSymbolicBuilder<F>>,
+{
+    let mut builder = MultiStageSymbolicBuilder::new(
+        air.preprocessed_width(),
+        air.width(),
+        air.aux_width(),
+        num_public_values,
+        air.num_challenges(),
+    );
+    air.eval(&mut builder);
+
+    let constraint_degree = builder
+        .constraints()
+        .iter()
+        .map(SymbolicExpression::degree_multiple)
+        .max()
+        .unwrap_or(0);
+
+    // The quotient's actual degree is approximately (max_constraint_degree - 1) n,
+    // where subtracting 1 comes from division by the zerofier.
+    // But we pad it to a power of two so that we can efficiently decompose the quotient.
+    log2_ceil_usize(max(constraint_degree, 2) - 1)
+}
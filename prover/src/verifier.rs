@@ -0,0 +1,266 @@
+use crate::{
+    get_log_quotient_degree, num_challenges, Com, MultiStageSymbolicBuilder, OpenedValues, Proof,
+    VerifierConstraintFolder,
+};
+use air::MultiStageAir;
+use p3_air::{Air, BaseAir};
+use p3_challenger::{CanObserve, CanSample, FieldChallenger};
+use p3_commit::{Pcs, PolynomialSpace};
+use p3_field::{Field, FieldAlgebra, FieldExtensionAlgebra};
+use p3_matrix::dense::RowMajorMatrixView;
+use p3_matrix::stack::VerticalPair;
+use p3_uni_stark::{Domain, PcsError, StarkGenericConfig, Val, VerificationError};
+use tracing::instrument;
+
+/// Verifies `proof` of the tables `airs`.
+///
+/// The lookup and permutation challenges are re-derived from the transcript after
+/// observing the main trace commitment, exactly as the prover did. `preprocessed` is the
+/// commitment of the preprocessed columns from the verifying key, if any table has them.
+#[instrument(skip_all)]
+pub fn verify<SC, A>(
+    config: &SC,
+    airs: &[A],
+    preprocessed: Option<&Com<SC>>,
+    challenger: &mut SC::Challenger,
+    proof: &Proof<SC>,
+    public_values: &Vec<Val<SC>>,
+) -> Result<(), VerificationError<PcsError<SC>>>
+where
+    SC: StarkGenericConfig,
+    A: MultiStageAir<Val<SC>>
+        + Air<MultiStageSymbolicBuilder<Val<SC>>>
+        + for<'a> Air<VerifierConstraintFolder<'a, SC>>,
+{
+    let Proof {
+        commitments,
+        opened_values,
+        opening_proof,
+        degree_bits,
+    } = proof;
+
+    if degree_bits.len() != airs.len() || opened_values.len() != airs.len() {
+        return Err(VerificationError::InvalidProofShape);
+    }
+
+    let pcs = config.pcs();
+
+    let trace_domains: Vec<Domain<SC>> = degree_bits
+        .iter()
+        .map(|log_degree| pcs.natural_domain_for_degree(1 << log_degree))
+        .collect();
+
+    let quotient_chunks_domains: Vec<Vec<Domain<SC>>> = airs
+        .iter()
+        .zip(&trace_domains)
+        .zip(degree_bits)
+        .map(|((air, trace_domain), log_degree)| {
+            let log_quotient_degree =
+                get_log_quotient_degree::<Val<SC>, A>(air, public_values.len());
+            trace_domain
+                .create_disjoint_domain(1 << (log_degree + log_quotient_degree))
+                .split_domains(1 << log_quotient_degree)
+        })
+        .collect();
+
+    for ((air, opened_values), domains) in
+        airs.iter().zip(opened_values).zip(&quotient_chunks_domains)
+    {
+        let main_width = <A as BaseAir<Val<SC>>>::width(air);
+        let aux_width = air.aux_width();
+        let preprocessed_width = air.preprocessed_width();
+        let valid_shape = opened_values.preprocessed_local.len() == preprocessed_width
+            && opened_values.preprocessed_next.len() == preprocessed_width
+            && opened_values.main_local.len() == main_width
+            && opened_values.main_next.len() == main_width
+            && opened_values.aux_local.len() == aux_width
+            && opened_values.aux_next.len() == aux_width
+            && opened_values.quotient_chunks.len() == domains.len()
+            && opened_values
+                .quotient_chunks
+                .iter()
+                .all(|qc| qc.len() == <SC::Challenge as FieldExtensionAlgebra<Val<SC>>>::D);
+        if !valid_shape {
+            return Err(VerificationError::InvalidProofShape);
+        }
+    }
+
+    let has_preprocessed = airs.iter().any(|air| air.preprocessed_width() > 0);
+    if has_preprocessed != preprocessed.is_some() {
+        return Err(VerificationError::InvalidProofShape);
+    }
+
+    if let Some(preprocessed) = preprocessed {
+        challenger.observe(preprocessed.clone());
+    }
+    for log_degree in degree_bits {
+        challenger.observe(Val::<SC>::from_canonical_usize(*log_degree));
+    }
+    challenger.observe(commitments.main.clone());
+    challenger.observe_slice(public_values);
+
+    let challenges: Vec<Val<SC>> = (0..num_challenges::<Val<SC>, A>(airs))
+        .map(|_| challenger.sample())
+        .collect();
+
+    challenger.observe(commitments.aux.clone());
+
+    let alpha: SC::Challenge = challenger.sample_ext_element();
+    challenger.observe(commitments.quotient_chunks.clone());
+
+    let zeta: SC::Challenge = challenger.sample_ext_element();
+
+    // Every table is opened at zeta and at the next point of its own domain
+    let mut main_openings = Vec::new();
+    let mut aux_openings = Vec::new();
+    let mut preprocessed_openings = Vec::new();
+    for ((air, trace_domain), opened_values) in airs.iter().zip(&trace_domains).zip(opened_values) {
+        let zeta_next = trace_domain.next_point(zeta).unwrap();
+        if air.preprocessed_width() > 0 {
+            preprocessed_openings.push((
+                *trace_domain,
+                vec![
+                    (zeta, opened_values.preprocessed_local.clone()),
+                    (zeta_next, opened_values.preprocessed_next.clone()),
+                ],
+            ));
+        }
+        main_openings.push((
+            *trace_domain,
+            vec![
+                (zeta, opened_values.main_local.clone()),
+                (zeta_next, opened_values.main_next.clone()),
+            ],
+        ));
+        aux_openings.push((
+            *trace_domain,
+            vec![
+                (zeta, opened_values.aux_local.clone()),
+                (zeta_next, opened_values.aux_next.clone()),
+            ],
+        ));
+    }
+
+    let mut rounds = vec![
+        (commitments.main.clone(), main_openings),
+        (commitments.aux.clone(), aux_openings),
+        (
+            commitments.quotient_chunks.clone(),
+            quotient_chunks_domains
+                .iter()
+                .zip(opened_values)
+                .flat_map(|(domains, opened_values)| {
+                    domains
+                        .iter()
+                        .zip(&opened_values.quotient_chunks)
+                        .map(|(domain, values)| (*domain, vec![(zeta, values.clone())]))
+                })
+                .collect(),
+        ),
+    ];
+    if let Some(preprocessed) = preprocessed {
+        rounds.push((preprocessed.clone(), preprocessed_openings));
+    }
+
+    pcs.verify(rounds, opening_proof, challenger)
+        .map_err(VerificationError::InvalidOpeningArgument)?;
+
+    for (((air, opened_values), trace_domain), domains) in airs
+        .iter()
+        .zip(opened_values)
+        .zip(&trace_domains)
+        .zip(&quotient_chunks_domains)
+    {
+        verify_constraints::<SC, A>(
+            air,
+            opened_values,
+            *trace_domain,
+            domains,
+            public_values,
+            &challenges,
+            alpha,
+            zeta,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the constraints of a table evaluated at `zeta` match its quotient.
+#[allow(clippy::too_many_arguments)]
+fn verify_constraints<SC, A>(
+    air: &A,
+    opened_values: &OpenedValues<SC::Challenge>,
+    trace_domain: Domain<SC>,
+    quotient_chunks_domains: &[Domain<SC>],
+    public_values: &[Val<SC>],
+    challenges: &[Val<SC>],
+    alpha: SC::Challenge,
+    zeta: SC::Challenge,
+) -> Result<(), VerificationError<PcsError<SC>>>
+where
+    SC: StarkGenericConfig,
+    A: for<'a> Air<VerifierConstraintFolder<'a, SC>>,
+{
+    let zps = quotient_chunks_domains
+        .iter()
+        .enumerate()
+        .map(|(i, domain)| {
+            quotient_chunks_domains
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other_domain)| {
+                    other_domain.zp_at_point(zeta)
+                        * other_domain.zp_at_point(domain.first_point()).inverse()
+                })
+                .product::<SC::Challenge>()
+        })
+        .collect::<Vec<_>>();
+
+    let quotient = opened_values
+        .quotient_chunks
+        .iter()
+        .enumerate()
+        .map(|(ch_i, ch)| {
+            ch.iter()
+                .enumerate()
+                .map(|(e_i, &c)| zps[ch_i] * SC::Challenge::monomial(e_i) * c)
+                .sum::<SC::Challenge>()
+        })
+        .sum::<SC::Challenge>();
+
+    let sels = trace_domain.selectors_at_point(zeta);
+
+    let mut folder = VerifierConstraintFolder {
+        main: VerticalPair::new(
+            RowMajorMatrixView::new_row(&opened_values.main_local),
+            RowMajorMatrixView::new_row(&opened_values.main_next),
+        ),
+        aux: VerticalPair::new(
+            RowMajorMatrixView::new_row(&opened_values.aux_local),
+            RowMajorMatrixView::new_row(&opened_values.aux_next),
+        ),
+        preprocessed: VerticalPair::new(
+            RowMajorMatrixView::new_row(&opened_values.preprocessed_local),
+            RowMajorMatrixView::new_row(&opened_values.preprocessed_next),
+        ),
+        public_values,
+        challenges,
+        is_first_row: sels.is_first_row,
+        is_last_row: sels.is_last_row,
+        is_transition: sels.is_transition,
+        alpha,
+        accumulator: SC::Challenge::ZERO,
+    };
+    air.eval(&mut folder);
+    let folded_constraints = folder.accumulator;
+
+    // Finally, check that
+    //     folded_constraints(zeta) / Z_H(zeta) = quotient(zeta)
+    if folded_constraints * sels.inv_zeroifier != quotient {
+        return Err(VerificationError::OodEvaluationMismatch);
+    }
+
+    Ok(())
+}
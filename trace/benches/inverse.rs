@@ -0,0 +1,62 @@
+//! Compares per-value inversion with the batch inversion used for the inverse columns.
+//!
+//! Sizes follow the `mxp` traces from `bench.log`: 2^19 rows, one A inverse column
+//! and one B inverse column per table.
+//!
+//! Run with `cargo bench -p trace --features parallel`.
+
+use p3_bls12_377_fr::Bls12_377Fr;
+use p3_field::Field;
+use rand::distributions::Standard;
+use rand::{thread_rng, Rng};
+use std::time::{Duration, Instant};
+use trace::inverse::{batch_inverse, batch_inverse_parallel, BATCH_INVERSE_CHUNK_SIZE};
+
+const LOG_HEIGHT: usize = 19;
+const COLUMNS: usize = 2;
+
+fn measure<R>(label: &str, f: impl Fn() -> R) -> Duration {
+    let start = Instant::now();
+    let res = f();
+    let elapsed = start.elapsed();
+    std::hint::black_box(res);
+
+    println!("{:<24} {:>10.2?}", label, elapsed);
+    elapsed
+}
+
+fn main() {
+    let mut rng = thread_rng();
+    let columns: Vec<Vec<Bls12_377Fr>> = (0..COLUMNS)
+        .map(|_| (0..1 << LOG_HEIGHT).map(|_| rng.sample(Standard)).collect())
+        .collect();
+
+    println!("{} columns of 2^{} rows", COLUMNS, LOG_HEIGHT);
+
+    let naive = measure("inverse per value", || {
+        columns
+            .iter()
+            .map(|c| c.iter().map(|v| v.inverse()).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    });
+
+    let batch = measure("batch inverse", || {
+        columns
+            .iter()
+            .map(|c| batch_inverse(c).unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let parallel = measure("chunked batch inverse", || {
+        columns
+            .iter()
+            .map(|c| batch_inverse_parallel(c, BATCH_INVERSE_CHUNK_SIZE).unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    println!(
+        "speedup: batch {:.1}x, chunked {:.1}x",
+        naive.as_secs_f64() / batch.as_secs_f64(),
+        naive.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
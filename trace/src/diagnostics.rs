@@ -0,0 +1,350 @@
+use crate::lookup::b_table_values;
+use air::air_lookup::AirLookupConfig;
+use air::air_permutation::AirPermutationConfig;
+use p3_bls12_377_fr::Bls12_377Fr;
+use p3_field::FieldAlgebra;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// Enabled A row of a lookup whose tuple is absent from all enabled B tables.
+#[derive(Debug, Clone)]
+pub struct MissingLookupRow {
+    pub row: usize,
+    pub values: Vec<Bls12_377Fr>,
+}
+
+/// Rows of one lookup argument that can't be found in B. Only the first `limit` rows
+/// are kept in `missing`, while `total_missing` counts all of them.
+#[derive(Debug, Clone)]
+pub struct LookupDiagnostics {
+    pub name: String,
+    /// Names of the A columns.
+    pub columns: Vec<String>,
+    pub missing: Vec<MissingLookupRow>,
+    pub total_missing: usize,
+}
+
+impl LookupDiagnostics {
+    /// Compares the exact A and B tuples, so the result doesn't depend on challenges.
+    /// `main` and `fixed` are the row-major main trace and preprocessed columns with their
+    /// widths.
+    pub(crate) fn new(
+        name: &str,
+        cfg: &AirLookupConfig,
+        main: (&[Bls12_377Fr], usize),
+        fixed: (&[Bls12_377Fr], usize),
+        limit: usize,
+    ) -> Self {
+        let mut b_tuples: HashSet<Vec<Bls12_377Fr>> = HashSet::new();
+        for (b_table_ind, b_columns_ids) in cfg.b_columns_ids.iter().enumerate() {
+            let b_filter_id = cfg.b_filter_id[b_table_ind];
+            let (b_values, b_width) = b_table_values(cfg, b_table_ind, main, fixed);
+            for row in b_values.chunks(b_width) {
+                if row[b_filter_id] != Bls12_377Fr::ZERO {
+                    b_tuples.insert(b_columns_ids.iter().map(|id| row[*id]).collect());
+                }
+            }
+        }
+
+        let mut missing = Vec::new();
+        let mut total_missing = 0;
+
+        let (main, width) = main;
+        for (i, row) in main.chunks(width).enumerate() {
+            if row[cfg.a_filter_id] == Bls12_377Fr::ZERO {
+                continue;
+            }
+
+            let values: Vec<Bls12_377Fr> = cfg.a_columns_ids.iter().map(|id| row[*id]).collect();
+
+            if !b_tuples.contains(&values) {
+                total_missing += 1;
+                if missing.len() < limit {
+                    missing.push(MissingLookupRow { row: i, values });
+                }
+            }
+        }
+
+        LookupDiagnostics {
+            name: name.to_string(),
+            columns: cfg.a_columns_names.clone(),
+            missing,
+            total_missing,
+        }
+    }
+}
+
+impl Display for LookupDiagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} enabled A rows are not found in B, columns ",
+            self.name, self.total_missing
+        )?;
+        write_names(f, &self.columns)?;
+
+        for missing_row in &self.missing {
+            write!(f, "\n  row {}: ", missing_row.row)?;
+            write_tuple(f, &missing_row.values)?;
+        }
+
+        if self.total_missing > self.missing.len() {
+            write!(
+                f,
+                "\n  ... and {} more",
+                self.total_missing - self.missing.len()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Tuple that occurs more times on one side of a permutation than on the other.
+#[derive(Debug, Clone)]
+pub struct UnmatchedTuple {
+    pub values: Vec<Bls12_377Fr>,
+    /// How many more times the tuple occurs on this side.
+    pub count: usize,
+    /// Rows of the unmatched occurrences.
+    pub rows: Vec<usize>,
+}
+
+/// Exact multiset difference between the enabled A and B rows of a permutation.
+#[derive(Debug, Clone, Default)]
+pub struct PermutationDifference {
+    /// Names of the A and B columns.
+    pub a_columns: Vec<String>,
+    pub b_columns: Vec<String>,
+    pub a_only: Vec<UnmatchedTuple>,
+    pub b_only: Vec<UnmatchedTuple>,
+}
+
+/// Amount of tuples per side and rows per tuple printed by [`PermutationDifference`].
+const MAX_DISPLAYED: usize = 10;
+
+impl PermutationDifference {
+    pub(crate) fn new(cfg: &AirPermutationConfig, main: &[Bls12_377Fr], width: usize) -> Self {
+        Self::compute(
+            (&cfg.a_columns_ids, cfg.a_filter_id),
+            (&cfg.b_columns_ids, cfg.b_filter_id),
+            (&cfg.a_columns_names, &cfg.b_columns_names),
+            main,
+            width,
+        )
+    }
+
+    /// Difference of a lookup with unit multiplicities, i.e. a permutation proved as a
+    /// lookup into its only B table.
+    pub(crate) fn from_lookup(cfg: &AirLookupConfig, main: &[Bls12_377Fr], width: usize) -> Self {
+        Self::compute(
+            (&cfg.a_columns_ids, Some(cfg.a_filter_id)),
+            (&cfg.b_columns_ids[0], Some(cfg.b_filter_id[0])),
+            (&cfg.a_columns_names, &cfg.b_columns_names[0]),
+            main,
+            width,
+        )
+    }
+
+    /// Difference of the A and B tuples, given by their ids and optional filter ids.
+    fn compute(
+        a: (&[usize], Option<usize>),
+        b: (&[usize], Option<usize>),
+        names: (&[String], &[String]),
+        main: &[Bls12_377Fr],
+        width: usize,
+    ) -> Self {
+        // Enabled rows of every tuple, in increasing order.
+        let occurrences = |ids: &[usize], filter_id: Option<usize>| {
+            let mut rows: HashMap<Vec<Bls12_377Fr>, Vec<usize>> = HashMap::new();
+            for (i, row) in main.chunks(width).enumerate() {
+                if filter_id.is_some_and(|id| row[id] == Bls12_377Fr::ZERO) {
+                    continue;
+                }
+                let values = ids.iter().map(|id| row[*id]).collect();
+                rows.entry(values).or_default().push(i);
+            }
+            rows
+        };
+
+        let a_rows = occurrences(a.0, a.1);
+        let mut b_rows = occurrences(b.0, b.1);
+
+        let mut difference = PermutationDifference {
+            a_columns: names.0.to_vec(),
+            b_columns: names.1.to_vec(),
+            ..Default::default()
+        };
+
+        for (values, a) in a_rows {
+            let b = b_rows.remove(&values).unwrap_or_default();
+
+            // Occurrences are matched in the row order, so the later ones stay unmatched.
+            if a.len() > b.len() {
+                difference.a_only.push(UnmatchedTuple {
+                    values,
+                    count: a.len() - b.len(),
+                    rows: a[b.len()..].to_vec(),
+                });
+            } else if b.len() > a.len() {
+                difference.b_only.push(UnmatchedTuple {
+                    values,
+                    count: b.len() - a.len(),
+                    rows: b[a.len()..].to_vec(),
+                });
+            }
+        }
+
+        difference
+            .b_only
+            .extend(b_rows.into_iter().map(|(values, rows)| UnmatchedTuple {
+                values,
+                count: rows.len(),
+                rows,
+            }));
+
+        difference.a_only.sort_by_key(|t| t.rows[0]);
+        difference.b_only.sort_by_key(|t| t.rows[0]);
+
+        difference
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.a_only.is_empty() && self.b_only.is_empty()
+    }
+}
+
+impl Display for PermutationDifference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sides = [
+            ("A", "B", &self.a_columns, &self.a_only),
+            ("B", "A", &self.b_columns, &self.b_only),
+        ];
+        for (side, other, columns, tuples) in sides {
+            if tuples.is_empty() {
+                continue;
+            }
+
+            write!(f, "\n  {} tuples in {} ", tuples.len(), side)?;
+            write_names(f, columns)?;
+            write!(f, " not in {}:", other)?;
+
+            for tuple in tuples.iter().take(MAX_DISPLAYED) {
+                write!(f, "\n    ")?;
+                write_tuple(f, &tuple.values)?;
+                write!(f, " x{} at rows ", tuple.count)?;
+                for (i, row) in tuple.rows.iter().take(MAX_DISPLAYED).enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", row)?;
+                }
+                if tuple.rows.len() > MAX_DISPLAYED {
+                    write!(f, ", ...")?;
+                }
+            }
+
+            if tuples.len() > MAX_DISPLAYED {
+                write!(f, "\n    ... and {} more", tuples.len() - MAX_DISPLAYED)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_tuple(f: &mut Formatter<'_>, values: &[Bls12_377Fr]) -> std::fmt::Result {
+    write!(f, "(")?;
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    write!(f, ")")
+}
+
+fn write_names(f: &mut Formatter<'_>, names: &[String]) -> std::fmt::Result {
+    write!(f, "({})", names.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TraceError;
+    use crate::testing::{lookup, permutation};
+    use crate::RawTrace;
+    use p3_bls12_377_fr::Bls12_377Fr;
+    use p3_field::FieldAlgebra;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn values(values: &[u64]) -> Vec<Bls12_377Fr> {
+        values
+            .iter()
+            .map(|v| Bls12_377Fr::from_canonical_u64(*v))
+            .collect()
+    }
+
+    #[test]
+    fn missing_lookup_rows_are_reported() {
+        let mut trace = RawTrace::new();
+        let lt = lookup("missing", &[1, 5, 2, 6, 7], &[&[1, 2, 3]]);
+        let cfgs = trace.push_traces(vec![], vec![lt], vec![]).unwrap();
+
+        let diagnostics = trace.diagnose_lookups(&cfgs, 2);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].name, "missing");
+        assert_eq!(diagnostics[0].total_missing, 3);
+
+        // Only the first rows within the limit are kept
+        let missing: Vec<_> = diagnostics[0]
+            .missing
+            .iter()
+            .map(|m| (m.row, m.values.clone()))
+            .collect();
+        assert_eq!(missing, vec![(1, values(&[5])), (3, values(&[6]))]);
+    }
+
+    #[test]
+    fn satisfied_lookups_are_not_reported() {
+        let mut trace = RawTrace::new();
+        let lt = lookup("found", &[3, 1, 3], &[&[1, 2, 3]]);
+        let cfgs = trace.push_traces(vec![], vec![lt], vec![]).unwrap();
+
+        assert!(trace.diagnose_lookups(&cfgs, 10).is_empty());
+    }
+
+    #[test]
+    fn permutation_difference_is_reported() {
+        let mut trace = RawTrace::new();
+        let pt = permutation("difference", &[1, 2, 2, 5], &[2, 1, 7, 7]);
+        let cfgs = trace.push_traces(vec![pt], vec![], vec![]).unwrap();
+
+        let mut rng = thread_rng();
+        let challenges: Vec<Bls12_377Fr> = (0..2).map(|_| rng.sample(Standard)).collect();
+        let Err(TraceError::UnsatisfiedPermutation { name, difference }) =
+            trace.get_aux_trace(&cfgs, &challenges)
+        else {
+            panic!("unsatisfied permutation expected");
+        };
+        assert_eq!(name, "difference");
+
+        // The second 2 in A is unmatched, both 7 in B are
+        let a_only: Vec<_> = difference
+            .a_only
+            .iter()
+            .map(|t| (t.values.clone(), t.count, t.rows.clone()))
+            .collect();
+        assert_eq!(
+            a_only,
+            vec![(values(&[2]), 1, vec![2]), (values(&[5]), 1, vec![3])]
+        );
+
+        let b_only: Vec<_> = difference
+            .b_only
+            .iter()
+            .map(|t| (t.values.clone(), t.count, t.rows.clone()))
+            .collect();
+        assert_eq!(b_only, vec![(values(&[7]), 2, vec![2, 3])]);
+    }
+}
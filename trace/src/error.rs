@@ -0,0 +1,119 @@
+use crate::diagnostics::PermutationDifference;
+use crate::validation::ShapeViolation;
+use std::fmt::{Debug, Display, Formatter};
+
+pub enum TraceError {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    Decode {
+        path: String,
+        message: String,
+    },
+    /// Decoded input failed validation; contains every violation found.
+    ShapeMismatch {
+        name: String,
+        violations: Vec<ShapeViolation>,
+    },
+    /// Argument can't be padded with zero rows without breaking it, since its columns
+    /// have different heights.
+    NonNeutralPadding {
+        name: String,
+        violations: Vec<ShapeViolation>,
+    },
+    /// Lookup running sum is not zero; `row` is the first enabled A row without a match.
+    UnsatisfiedLookup {
+        name: String,
+        row: usize,
+    },
+    /// Permutation running product is not one; `difference` holds the A and B rows that
+    /// don't have a pair on the other side.
+    UnsatisfiedPermutation {
+        name: String,
+        difference: PermutationDifference,
+    },
+    /// Argument provides another values of a shared column than the argument that
+    /// introduced it; `row` is the first differing row.
+    SharedColumnMismatch {
+        name: String,
+        column: String,
+        row: usize,
+    },
+    BadChallengeCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Combination of an argument row with the challenges is zero and can't be inverted;
+    /// `row` is the first such row.
+    ZeroCombination {
+        name: String,
+        row: usize,
+    },
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceError::Io { path, source } => write!(f, "{}: {}", path, source),
+            TraceError::Decode { path, message } => {
+                write!(f, "{}: failed to decode trace: {}", path, message)
+            }
+            TraceError::ShapeMismatch { name, violations } => {
+                write!(f, "{}: invalid shape:", name)?;
+                violations
+                    .iter()
+                    .try_for_each(|violation| write!(f, "\n  - {}", violation))
+            }
+            TraceError::NonNeutralPadding { name, violations } => {
+                write!(f, "{}: can't be padded with neutral rows:", name)?;
+                violations
+                    .iter()
+                    .try_for_each(|violation| write!(f, "\n  - {}", violation))
+            }
+            TraceError::UnsatisfiedLookup { name, row } => write!(
+                f,
+                "{}: lookup is not satisfied, A row {} is not found in B",
+                name, row
+            ),
+            TraceError::UnsatisfiedPermutation { name, difference } => {
+                write!(f, "{}: permutation is not satisfied{}", name, difference)
+            }
+            TraceError::SharedColumnMismatch { name, column, row } => write!(
+                f,
+                "{}: column {} differs from the shared one at row {}",
+                name, column, row
+            ),
+            TraceError::BadChallengeCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: {} challenges expected, {} provided",
+                name, expected, found
+            ),
+            TraceError::ZeroCombination { name, row } => write!(
+                f,
+                "{}: row {} combines to zero with the challenges and can't be inverted",
+                name, row
+            ),
+        }
+    }
+}
+
+impl Debug for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for TraceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TraceError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::error::TraceError;
+use p3_bls12_377_fr::Bls12_377Fr;
+use p3_field::Field;
+use p3_maybe_rayon::prelude::*;
+
+/// Amount of values inverted together by [`batch_inverse_parallel`]. Costs one field
+/// inversion per chunk, which is negligible next to `3 * BATCH_INVERSE_CHUNK_SIZE`
+/// multiplications.
+pub const BATCH_INVERSE_CHUNK_SIZE: usize = 1 << 12;
+
+/// Inverts all values with Montgomery's trick: a single field inversion and three
+/// multiplications per value.
+///
+/// Returns `None` if any value is zero, same as `Field::try_inverse`.
+pub fn batch_inverse<F: Field>(values: &[F]) -> Option<Vec<F>> {
+    let mut res = vec![F::ZERO; values.len()];
+    batch_inverse_into(values, &mut res)?;
+    Some(res)
+}
+
+/// Same as [`batch_inverse`], but splits values into chunks of `chunk_size` which are
+/// inverted independently, in parallel with the `parallel` feature.
+pub fn batch_inverse_parallel<F: Field>(values: &[F], chunk_size: usize) -> Option<Vec<F>> {
+    let mut res = vec![F::ZERO; values.len()];
+
+    values
+        .par_chunks(chunk_size)
+        .zip(res.par_chunks_mut(chunk_size))
+        .try_for_each(|(values, res)| batch_inverse_into(values, res))?;
+
+    Some(res)
+}
+
+/// Inverts the row combinations of the argument `name`. A combination is zero only if the
+/// challenges happen to cancel a row, which is reported with the first such row.
+pub(crate) fn inverse_combs(
+    name: &str,
+    combs: &[Bls12_377Fr],
+) -> Result<Vec<Bls12_377Fr>, TraceError> {
+    batch_inverse_parallel(combs, BATCH_INVERSE_CHUNK_SIZE).ok_or_else(|| {
+        TraceError::ZeroCombination {
+            name: name.to_string(),
+            row: combs.iter().position(|comb| comb.is_zero()).unwrap_or(0),
+        }
+    })
+}
+
+fn batch_inverse_into<F: Field>(values: &[F], res: &mut [F]) -> Option<()> {
+    // Prefix products: `res[i] = values[0] * ... * values[i - 1]`
+    let mut acc = F::ONE;
+    for (value, r) in values.iter().zip(res.iter_mut()) {
+        *r = acc;
+        acc *= *value;
+    }
+
+    let mut inverse = acc.try_inverse()?;
+
+    // Walking back, `inverse` is `(values[0] * ... * values[i])^-1` before the update
+    for (value, r) in values.iter().zip(res.iter_mut()).rev() {
+        *r *= inverse;
+        inverse *= *value;
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p3_field::FieldAlgebra;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn batch_inverse_matches_field_inverse() {
+        let mut rng = thread_rng();
+
+        // Three full chunks and a shorter last one
+        let chunk_size = 64;
+        let values: Vec<Bls12_377Fr> = (0..3 * chunk_size + 5)
+            .map(|_| rng.sample(Standard))
+            .collect();
+        let expected: Vec<Bls12_377Fr> = values.iter().map(|v| v.inverse()).collect();
+
+        assert_eq!(batch_inverse(&values).unwrap(), expected);
+        assert_eq!(
+            batch_inverse_parallel(&values, chunk_size).unwrap(),
+            expected
+        );
+        assert_eq!(
+            batch_inverse_parallel(&values, BATCH_INVERSE_CHUNK_SIZE).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn zero_combination_is_reported() {
+        let mut combs: Vec<Bls12_377Fr> = (1..200).map(Bls12_377Fr::from_canonical_u32).collect();
+        combs[130] = Bls12_377Fr::ZERO;
+
+        assert!(batch_inverse(&combs).is_none());
+        assert!(batch_inverse_parallel(&combs, 64).is_none());
+        assert!(matches!(
+            inverse_combs("zero", &combs),
+            Err(TraceError::ZeroCombination { row: 130, .. })
+        ));
+    }
+}
@@ -1,107 +1,659 @@
+pub mod diagnostics;
+pub mod error;
+pub mod inverse;
 pub mod lookup;
 pub mod permutation;
+pub mod range;
+mod scan;
+pub mod tables;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod validation;
 
+use crate::diagnostics::LookupDiagnostics;
+use crate::error::TraceError;
 use crate::lookup::RawLookupTrace;
 use crate::permutation::RawPermutationTrace;
-use air::air_lookup::AirLookupConfig;
-use air::air_permutation::AirPermutationConfig;
-use air::AirConfig;
+use crate::range::RawRangeTrace;
+use crate::validation::ShapeViolation;
+use air::preprocessed::{self, FixedColumn};
+use air::{AirConfig, NUM_CHALLENGES};
 use ark_ff::PrimeField;
-use p3_air::Air;
-use p3_bls12_377_fr::Bls12_377Fr;
-use p3_field::{Field, FieldAlgebra};
+use p3_bls12_377_fr::{Bls12_377Fr, FF_Bls12_377Fr};
+use p3_field::FieldAlgebra;
 use p3_matrix::dense::RowMajorMatrix;
-use serde::{Deserialize, Serialize};
+use p3_maybe_rayon::prelude::*;
+use serde::de::DeserializeOwned;
 use std::cmp::max;
+use std::fs;
 
 pub struct RawTrace {
-    pub columns: Vec<Vec<Bls12_377Fr>>,
+    /// Row-major main trace values, committed before the challenges are sampled.
+    /// Arguments occupy consecutive columns in the order they were pushed, except for the
+    /// columns shared with earlier arguments.
+    pub main: Vec<Bls12_377Fr>,
+    /// Total width of the main trace of all pushed arguments.
+    pub width: usize,
+    /// Total width of the auxiliary trace of all pushed arguments.
+    pub aux_width: usize,
+    /// Power of two height of the padded trace.
     pub height: usize,
-    pub challenges: Vec<Bls12_377Fr>,
+    /// Lower bound of `height`.
+    pub min_height: usize,
+    /// Names of the pushed arguments in the order of their configs.
+    pub names: Vec<String>,
+    /// Names of the main trace columns, empty for unnamed ones. Arguments referencing a
+    /// column of the same name share it.
+    pub column_names: Vec<String>,
+    /// Preprocessed columns of all pushed arguments. Equal columns are committed once.
+    pub preprocessed: Vec<FixedColumn>,
+    /// Row-major values of the preprocessed columns, padded to `height`.
+    pub fixed: Vec<Bls12_377Fr>,
+    /// Permutations are proved as lookups with unit multiplicities, and all lookups add
+    /// their log-derivative terms into a single running sum.
+    pub log_derivative_permutations: bool,
+    /// Auxiliary id of the running sum shared by the lookups, once one is laid out.
+    pub accumulator: Option<usize>,
+    /// Amount of lookups sharing the running sum, their tags are `1..=accumulated`.
+    pub accumulated: usize,
+}
+
+impl Default for RawTrace {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RawTrace {
-    pub fn new(challenges: Vec<Bls12_377Fr>) -> Self {
+    pub fn new() -> Self {
         RawTrace {
-            columns: vec![],
+            main: vec![],
+            width: 0,
+            aux_width: 0,
             height: 0,
-            challenges,
+            min_height: 1,
+            names: vec![],
+            column_names: vec![],
+            preprocessed: vec![],
+            fixed: vec![],
+            log_derivative_permutations: false,
+            accumulator: None,
+            accumulated: 0,
         }
     }
-    pub fn resize(&mut self, new_size: usize) {
-        for e in &mut self.columns {
-            e.resize(new_size, Bls12_377Fr::ZERO);
+
+    pub fn with_min_height(min_height: usize) -> Self {
+        RawTrace {
+            min_height,
+            ..Self::new()
         }
     }
 
-    pub fn push_lookup(&mut self, lookup: RawLookupTrace) -> AirConfig {
-        let mut l = lookup.clone();
+    /// Appends the arguments to the main trace, lookups first and range checks last. Layouts
+    /// only depend on the amount of columns, so the whole main trace is allocated at once and
+    /// every raw column is decoded right into its place. Pushing into a non-empty trace
+    /// reallocates it and copies the already pushed arguments, so `RawTables` pushes all
+    /// arguments of a table in one call.
+    ///
+    /// The trace is padded with zero rows up to a power of two height. Zero filters disable
+    /// the padded lookup and filtered permutation rows, and the padded rows of unfiltered
+    /// permutations match each other, so this only requires all columns of an argument to
+    /// have the same height.
+    ///
+    /// Columns with the same name are committed once, see `lay_out`, and must hold the same
+    /// values in every argument. Constant tables go to the preprocessed columns instead.
+    pub fn push_traces(
+        &mut self,
+        mut permutation_traces: Vec<RawPermutationTrace>,
+        mut lookup_traces: Vec<RawLookupTrace>,
+        mut range_traces: Vec<RawRangeTrace>,
+    ) -> Result<Vec<AirConfig>, TraceError> {
+        for lt in &mut lookup_traces {
+            check_padding(&lt.name, lt.validate())?;
+            lt.fill_default_filters();
+        }
+
+        for pt in &mut permutation_traces {
+            check_padding(&pt.name, pt.validate())?;
+            pt.fill_default_filters();
+            if self.log_derivative_permutations {
+                pt.enable_all_rows();
+            }
+        }
 
-        // Resize trace according to the max height
-        l.resize(self.height);
+        for rt in &mut range_traces {
+            check_padding(&rt.name, rt.validate())?;
+            rt.fill_defaults();
+        }
 
-        let (mut cfg, mut lookup_columns) = l.get_trace(self.challenges.clone());
-        cfg.shift(self.columns.len());
-        self.columns.append(&mut lookup_columns);
+        let prev_width = self.width;
+        let lookups: Vec<&RawLookupTrace> = lookup_traces.iter().collect();
+        let permutations: Vec<&RawPermutationTrace> = permutation_traces.iter().collect();
+        let ranges: Vec<&RawRangeTrace> = range_traces.iter().collect();
+        let layouts = self.lay_out_traces(&lookups, &permutations, &ranges);
+        let height = self.height;
 
-        AirConfig::Lookup(cfg)
-    }
+        let mut cfgs = Vec::new();
+        // Raw columns to decode and the columns shared with already laid out ones, with
+        // their main trace ids
+        let mut columns: Vec<(usize, &[[u8; 32]])> = Vec::new();
+        let mut shared: Vec<(usize, &[[u8; 32]], &str)> = Vec::new();
+
+        let raw_columns = lookup_traces
+            .iter()
+            .map(|lt| (&lt.name, lt.main_columns()))
+            .chain(
+                permutation_traces
+                    .iter()
+                    .map(|pt| (&pt.name, pt.main_columns())),
+            )
+            .chain(range_traces.iter().map(|rt| (&rt.name, rt.main_columns())));
+
+        for ((cfg, ids), (name, raw_columns)) in layouts.into_iter().zip(raw_columns) {
+            for (id, column) in ids.into_iter().zip(raw_columns) {
+                if id >= prev_width && !columns.iter().any(|(i, _)| *i == id) {
+                    columns.push((id, column));
+                } else {
+                    shared.push((id, column, name.as_str()));
+                }
+            }
+            cfgs.push(cfg);
+        }
+
+        let width = self.width;
+        let mut main = vec![Bls12_377Fr::ZERO; width * height];
+
+        // Keep the previously pushed arguments
+        if prev_width > 0 {
+            main.par_chunks_mut(width)
+                .zip(self.main.par_chunks(prev_width))
+                .for_each(|(row, prev_row)| row[..prev_width].copy_from_slice(prev_row));
+        }
+
+        let (ids, columns): (Vec<usize>, Vec<&[[u8; 32]]>) = columns.into_iter().unzip();
+        write_columns(&mut main, width, &ids, &columns, |column, row| {
+            column.get(row).map_or(Bls12_377Fr::ZERO, decode)
+        });
+
+        // Shared columns are committed once, so all their copies must be equal
+        for (id, column, name) in shared {
+            let mismatch = main
+                .par_chunks(width)
+                .enumerate()
+                .filter(|(row, values)| {
+                    column.get(*row).map_or(Bls12_377Fr::ZERO, decode) != values[id]
+                })
+                .map(|(row, _)| row)
+                .min();
+            if let Some(row) = mismatch {
+                return Err(TraceError::SharedColumnMismatch {
+                    name: name.to_string(),
+                    column: self.column_names[id].clone(),
+                    row,
+                });
+            }
+        }
+
+        self.main = main;
+        self.fixed = preprocessed::row_major_values(&self.preprocessed, height);
+        self.height = height;
 
-    pub fn push_permutation(&mut self, permutation: RawPermutationTrace) -> AirConfig {
-        let mut p = permutation.clone();
-        // Resize trace according to the max height
-        p.resize(self.height);
+        Ok(cfgs)
+    }
 
-        let (mut cfg, mut permutation_columns) = p.get_trace(self.challenges.clone());
-        cfg.shift(self.columns.len());
-        self.columns.append(&mut permutation_columns);
+    /// Lays out the arguments as `push_traces` does, without decoding them, and raises the
+    /// height to fit them. Returns the shifted config and the main trace ids of the raw
+    /// columns of every argument, lookups first and range checks last. Expects the defaults
+    /// to be filled, except for the filters enabled by `enable_all_rows`.
+    pub fn lay_out_traces(
+        &mut self,
+        lookup_traces: &[&RawLookupTrace],
+        permutation_traces: &[&RawPermutationTrace],
+        range_traces: &[&RawRangeTrace],
+    ) -> Vec<(AirConfig, Vec<usize>)> {
+        let max_height = lookup_traces
+            .iter()
+            .map(|lt| lt.get_max_height())
+            .chain(permutation_traces.iter().map(|pt| pt.get_max_height()))
+            // Range tables included
+            .chain(range_traces.iter().map(|rt| rt.get_max_height()))
+            .fold(self.height, max);
+        self.height = padded_height(max_height, self.min_height);
 
-        AirConfig::Permutation(cfg)
+        argument_layouts(
+            lookup_traces,
+            permutation_traces,
+            range_traces,
+            self.log_derivative_permutations,
+        )
+        .map(|(name, cfg, column_names, fixed_columns)| {
+            self.lay_out(name, cfg, &column_names, fixed_columns)
+        })
+        .collect()
     }
 
-    pub fn push_traces(
+    /// Places the main columns of an argument with the given unshifted config, named in the
+    /// order of its raw layout. Named columns already present in the trace are reused, the
+    /// others are appended. Returns the shifted config and the main trace id of every raw
+    /// column. Only the layout is updated, the columns are written by `push_traces`.
+    ///
+    /// Preprocessed columns of the argument are reused if an equal one is already present.
+    /// With `log_derivative_permutations`, lookups are tagged and share the running sum of
+    /// the first one.
+    pub fn lay_out(
         &mut self,
-        permutation_traces: Vec<RawPermutationTrace>,
-        lookup_traces: Vec<RawLookupTrace>,
-    ) -> Vec<AirConfig> {
-        // Get max height of all lookup traces.
-        let mut lookup_max_height = 0;
-        lookup_traces.iter().for_each(|lt| {
-            lookup_max_height = max(lookup_max_height, lt.get_max_height());
-        });
+        name: &str,
+        mut cfg: AirConfig,
+        column_names: &[String],
+        fixed_columns: Vec<FixedColumn>,
+    ) -> (AirConfig, Vec<usize>) {
+        let ids: Vec<usize> = column_names
+            .iter()
+            .map(|column_name| {
+                let existing = self
+                    .column_names
+                    .iter()
+                    .position(|n| !column_name.is_empty() && n == column_name);
+                existing.unwrap_or_else(|| {
+                    self.column_names.push(column_name.clone());
+                    self.column_names.len() - 1
+                })
+            })
+            .collect();
 
-        // Get max height of all permutation traces.
-        let mut permutation_max_height = 0;
-        permutation_traces.iter().for_each(|pt| {
-            permutation_max_height = max(permutation_max_height, pt.get_max_height());
-        });
+        let fixed_ids: Vec<usize> = fixed_columns
+            .into_iter()
+            .map(|column| {
+                let existing = self.preprocessed.iter().position(|c| *c == column);
+                existing.unwrap_or_else(|| {
+                    self.preprocessed.push(column);
+                    self.preprocessed.len() - 1
+                })
+            })
+            .collect();
 
-        // Get trace max height.
-        self.height = max(permutation_max_height, lookup_max_height);
+        if self.log_derivative_permutations {
+            if let AirConfig::Lookup(l) = &mut cfg {
+                self.accumulated += 1;
+                l.tag = self.accumulated;
+                l.shared_check = self.accumulator.is_some();
+            }
+        }
 
-        let mut cfgs = Vec::new();
-        lookup_traces.iter().for_each(|lt| {
-            cfgs.push(self.push_lookup(lt.clone()));
-        });
+        cfg.shift(0, self.aux_width);
+        cfg.map_main_ids(|id| ids[id]);
+        cfg.map_preprocessed_ids(|id| fixed_ids[id]);
 
-        permutation_traces.iter().for_each(|pt| {
-            cfgs.push(self.push_permutation(pt.clone()));
-        });
+        if self.log_derivative_permutations {
+            if let AirConfig::Lookup(l) = &mut cfg {
+                match self.accumulator {
+                    Some(accumulator) => l.check_id = accumulator,
+                    None => self.accumulator = Some(l.check_id),
+                }
+            }
+        }
+
+        self.width = self.column_names.len();
+        self.aux_width += cfg.aux_width();
+        self.names.push(name.to_string());
 
-        cfgs
+        (cfg, ids)
     }
 
+    /// Returns a copy of the main trace. It only contains the input columns, so it can be
+    /// committed before any challenge is known.
+    ///
+    /// The copy is consumed by the commitment, while `main` is kept to generate the
+    /// auxiliary trace, so the peak memory is two copies of the main trace until the
+    /// commitment replaces one of them with its low-degree extension.
     pub fn get_trace(&self) -> RowMajorMatrix<Bls12_377Fr> {
-        let width = self.columns.len();
-        // The final trace
-        let mut values = vec![];
+        RowMajorMatrix::new(self.main.clone(), self.width)
+    }
+
+    /// Returns the preprocessed columns, if any argument has them.
+    pub fn get_preprocessed_trace(&self) -> Option<RowMajorMatrix<Bls12_377Fr>> {
+        (!self.preprocessed.is_empty())
+            .then(|| RowMajorMatrix::new(self.fixed.clone(), self.preprocessed.len()))
+    }
+
+    /// Returns the auxiliary trace of all arguments for the challenges sampled
+    /// after committing the main trace. Fails on the first unsatisfied argument.
+    pub fn get_aux_trace(
+        &self,
+        cfgs: &[AirConfig],
+        challenges: &[Bls12_377Fr],
+    ) -> Result<RowMajorMatrix<Bls12_377Fr>, TraceError> {
+        Ok(self.build_aux_trace(cfgs, challenges, true)?.0)
+    }
+
+    /// Same as `get_aux_trace`, but keeps the columns of the unsatisfied arguments and
+    /// returns their failures, so that their constraints can still be evaluated.
+    pub fn get_unchecked_aux_trace(
+        &self,
+        cfgs: &[AirConfig],
+        challenges: &[Bls12_377Fr],
+    ) -> Result<(RowMajorMatrix<Bls12_377Fr>, Vec<TraceError>), TraceError> {
+        self.build_aux_trace(cfgs, challenges, false)
+    }
+
+    fn build_aux_trace(
+        &self,
+        cfgs: &[AirConfig],
+        challenges: &[Bls12_377Fr],
+        checked: bool,
+    ) -> Result<(RowMajorMatrix<Bls12_377Fr>, Vec<TraceError>), TraceError> {
+        let mut aux = vec![Bls12_377Fr::ZERO; self.aux_width * self.height];
+        let mut failures = Vec::new();
+
+        for (cfg, name) in cfgs.iter().zip(&self.names) {
+            let mut aux_columns = if checked {
+                self.get_argument_aux_trace(name, cfg, challenges)?
+            } else {
+                let (aux_columns, failure) =
+                    self.get_argument_unchecked_aux_trace(name, cfg, challenges)?;
+                failures.extend(failure);
+                aux_columns
+            };
+            let mut ids = cfg.aux_columns_ids();
+
+            // Terms of a shared running sum are added to the ones of the earlier lookups,
+            // which is the running sum of all of them
+            if let AirConfig::Lookup(l) = cfg {
+                if l.shared_check {
+                    let check = aux_columns.pop().unwrap();
+                    ids.pop();
+                    aux.par_chunks_mut(self.aux_width)
+                        .zip(check.par_iter())
+                        .for_each(|(row, value)| row[l.check_id] += *value);
+                }
+            }
+
+            write_columns(
+                &mut aux,
+                self.aux_width,
+                &ids,
+                &aux_columns,
+                |column, row| column[row],
+            );
+        }
+
+        Ok((RowMajorMatrix::new(aux, self.aux_width), failures))
+    }
+
+    /// Checks every argument and returns all failures, unlike `get_aux_trace`.
+    pub fn check(&self, cfgs: &[AirConfig], challenges: &[Bls12_377Fr]) -> Vec<TraceError> {
+        cfgs.iter()
+            .zip(&self.names)
+            .filter_map(|(cfg, name)| self.get_argument_aux_trace(name, cfg, challenges).err())
+            .collect()
+    }
+
+    /// Lists the A rows of every lookup that are absent from B, at most `limit` rows per
+    /// lookup. Satisfied lookups are omitted.
+    pub fn diagnose_lookups(&self, cfgs: &[AirConfig], limit: usize) -> Vec<LookupDiagnostics> {
+        cfgs.iter()
+            .zip(&self.names)
+            .filter_map(|(cfg, name)| match cfg {
+                // Lookups with unit multiplicities report their permutation differences
+                AirConfig::Lookup(l) if !l.unit_multiplicity => Some(LookupDiagnostics::new(
+                    name,
+                    l,
+                    (&self.main, self.width),
+                    (&self.fixed, self.preprocessed.len()),
+                    limit,
+                )),
+                AirConfig::Lookup(_) | AirConfig::Permutation(_) | AirConfig::Range(_) => None,
+            })
+            .filter(|diagnostics| diagnostics.total_missing > 0)
+            .collect()
+    }
+
+    fn get_argument_aux_trace(
+        &self,
+        name: &str,
+        cfg: &AirConfig,
+        challenges: &[Bls12_377Fr],
+    ) -> Result<Vec<Vec<Bls12_377Fr>>, TraceError> {
+        match cfg {
+            AirConfig::Lookup(l) => lookup::get_aux_trace(
+                name,
+                l,
+                &self.main,
+                self.width,
+                &self.fixed,
+                self.preprocessed.len(),
+                challenges,
+            ),
+            AirConfig::Permutation(p) => {
+                permutation::get_aux_trace(name, p, &self.main, self.width, challenges)
+            }
+            AirConfig::Range(r) => range::get_aux_trace(
+                name,
+                r,
+                &self.main,
+                self.width,
+                &self.fixed,
+                self.preprocessed.len(),
+                challenges,
+            ),
+        }
+    }
+
+    fn get_argument_unchecked_aux_trace(
+        &self,
+        name: &str,
+        cfg: &AirConfig,
+        challenges: &[Bls12_377Fr],
+    ) -> Result<(Vec<Vec<Bls12_377Fr>>, Option<TraceError>), TraceError> {
+        match cfg {
+            AirConfig::Lookup(l) => lookup::get_unchecked_aux_trace(
+                name,
+                l,
+                &self.main,
+                self.width,
+                &self.fixed,
+                self.preprocessed.len(),
+                challenges,
+            ),
+            AirConfig::Permutation(p) => {
+                permutation::get_unchecked_aux_trace(name, p, &self.main, self.width, challenges)
+            }
+            AirConfig::Range(r) => range::get_unchecked_aux_trace(
+                name,
+                r,
+                &self.main,
+                self.width,
+                &self.fixed,
+                self.preprocessed.len(),
+                challenges,
+            ),
+        }
+    }
+}
+
+/// Name, unshifted config, main column names and preprocessed columns of every argument,
+/// lookups first and range checks last. With `log_derivative_permutations`, permutations are
+/// laid out as lookups with both filters, as if `enable_all_rows` was applied.
+pub fn argument_layouts<'a>(
+    lookup_traces: &'a [&RawLookupTrace],
+    permutation_traces: &'a [&RawPermutationTrace],
+    range_traces: &'a [&RawRangeTrace],
+    log_derivative_permutations: bool,
+) -> impl Iterator<Item = (&'a str, AirConfig, Vec<String>, Vec<FixedColumn>)> + 'a {
+    let lookups = lookup_traces.iter().map(|lt| {
+        let cfg = AirConfig::Lookup(lt.get_air_config());
+        (
+            lt.name.as_str(),
+            cfg,
+            lt.main_column_names(),
+            lt.fixed_columns(),
+        )
+    });
+
+    let permutations = permutation_traces.iter().map(move |pt| {
+        let mut column_names = pt.main_column_names();
+        let cfg = if log_derivative_permutations {
+            if !pt.is_filtered() {
+                column_names.push(pt.a_filter_name.clone());
+                column_names.push(pt.b_filter_name.clone());
+            }
+            AirConfig::Lookup(pt.get_log_derivative_air_config())
+        } else {
+            AirConfig::Permutation(pt.get_air_config())
+        };
+        (pt.name.as_str(), cfg, column_names, vec![])
+    });
+
+    let ranges = range_traces.iter().map(|rt| {
+        let cfg = AirConfig::Range(rt.get_air_config());
+        (
+            rt.name.as_str(),
+            cfg,
+            rt.main_column_names(),
+            rt.fixed_columns(),
+        )
+    });
+
+    lookups.chain(permutations).chain(ranges)
+}
+
+/// Height of the trace with arguments of at most `max_height` rows: the next power of two,
+/// but not less than `min_height`.
+pub fn padded_height(max_height: usize, min_height: usize) -> usize {
+    max(max_height, min_height).next_power_of_two()
+}
+
+/// Reports the violations of an argument pushed without `read_file`. Only columns of
+/// different heights block the neutral padding, anything else is an invalid shape.
+fn check_padding(name: &str, violations: Vec<ShapeViolation>) -> Result<(), TraceError> {
+    if violations.is_empty() {
+        Ok(())
+    } else if violations.iter().all(ShapeViolation::blocks_padding) {
+        Err(TraceError::NonNeutralPadding {
+            name: name.to_string(),
+            violations,
+        })
+    } else {
+        Err(TraceError::ShapeMismatch {
+            name: name.to_string(),
+            violations,
+        })
+    }
+}
+
+/// Rows per task of [`write_columns`]. A block of every column fits in cache, so that
+/// columns are read sequentially while the rows of the block are written.
+const TRANSPOSE_BLOCK_ROWS: usize = 1 << 8;
+
+/// Blocked transposition of `columns` into the columns `ids` of the row-major `values` of
+/// the given width. `get` returns the value of a column at a row.
+fn write_columns<C, G>(
+    values: &mut [Bls12_377Fr],
+    width: usize,
+    ids: &[usize],
+    columns: &[C],
+    get: G,
+) where
+    C: Sync,
+    G: Fn(&C, usize) -> Bls12_377Fr + Sync,
+{
+    if columns.is_empty() {
+        return;
+    }
 
-        for row in 0..self.height {
-            for col in 0..width {
-                values.push(self.columns[col][row]);
+    values
+        .par_chunks_mut(width * TRANSPOSE_BLOCK_ROWS)
+        .enumerate()
+        .for_each(|(block, block_values)| {
+            let first_row = block * TRANSPOSE_BLOCK_ROWS;
+            for (id, column) in ids.iter().zip(columns) {
+                for (i, row) in block_values.chunks_mut(width).enumerate() {
+                    row[*id] = get(column, first_row + i);
+                }
             }
+        });
+}
+
+/// Reads a CBOR raw trace file and checks its shape, `name` of the decoded input is used
+/// to report the violations.
+pub(crate) fn read_input<T, V, N>(path: &str, validate: V, name: N) -> Result<T, TraceError>
+where
+    T: DeserializeOwned,
+    V: FnOnce(&T) -> Vec<ShapeViolation>,
+    N: FnOnce(&T) -> String,
+{
+    let file_content = fs::read(path).map_err(|e| TraceError::Io {
+        path: path.to_string(),
+        source: e,
+    })?;
+    let raw_trace: T = ciborium::from_reader(std::io::Cursor::new(file_content)).map_err(|e| {
+        TraceError::Decode {
+            path: path.to_string(),
+            message: format!("{:?}", e),
         }
+    })?;
 
-        RowMajorMatrix::new(values, width)
+    let violations = validate(&raw_trace);
+    if !violations.is_empty() {
+        return Err(TraceError::ShapeMismatch {
+            name: name(&raw_trace),
+            violations,
+        });
     }
+
+    Ok(raw_trace)
+}
+
+/// Returns the `alpha` and `delta` challenges, or an error if another amount is given.
+pub(crate) fn unpack_challenges(
+    name: &str,
+    challenges: &[Bls12_377Fr],
+) -> Result<(Bls12_377Fr, Bls12_377Fr), TraceError> {
+    if challenges.len() != NUM_CHALLENGES {
+        return Err(TraceError::BadChallengeCount {
+            name: name.to_string(),
+            expected: NUM_CHALLENGES,
+            found: challenges.len(),
+        });
+    }
+
+    Ok((challenges[0], challenges[1]))
+}
+
+/// Decodes a big-endian field element of the raw trace formats.
+pub(crate) fn decode(bytes: &[u8; 32]) -> Bls12_377Fr {
+    Bls12_377Fr::new(FF_Bls12_377Fr::from_be_bytes_mod_order(bytes))
+}
+
+/// Copy of a single column of the row-major main trace.
+pub(crate) fn get_column(main: &[Bls12_377Fr], width: usize, id: usize) -> Vec<Bls12_377Fr> {
+    main.par_chunks(width).map(|row| row[id]).collect()
+}
+
+/// Linear combination of the given columns of every main trace row and the shifting
+/// challenge: `row[ids[0]] * alpha^(n-1) + ... + row[ids[n-1]] + delta`.
+pub(crate) fn combine_rows(
+    main: &[Bls12_377Fr],
+    width: usize,
+    ids: &[usize],
+    alpha: Bls12_377Fr,
+    delta: Bls12_377Fr,
+) -> Vec<Bls12_377Fr> {
+    combine_tagged_rows(main, width, 0, ids, alpha, delta)
+}
+
+/// Same as `combine_rows`, but with the `tag` prepended to every row:
+/// `tag * alpha^n + row[ids[0]] * alpha^(n-1) + ... + row[ids[n-1]] + delta`.
+pub(crate) fn combine_tagged_rows(
+    main: &[Bls12_377Fr],
+    width: usize,
+    tag: usize,
+    ids: &[usize],
+    alpha: Bls12_377Fr,
+    delta: Bls12_377Fr,
+) -> Vec<Bls12_377Fr> {
+    let tag = Bls12_377Fr::from_canonical_usize(tag);
+    main.par_chunks(width)
+        .map(|row| ids.iter().fold(tag, |comb, id| comb * alpha + row[*id]) + delta)
+        .collect()
 }
@@ -1,11 +1,16 @@
+use crate::diagnostics::PermutationDifference;
+use crate::error::TraceError;
+use crate::inverse::inverse_combs;
+use crate::scan::parallel_scan;
+use crate::{combine_tagged_rows, get_column, read_input, unpack_challenges};
 use air::air_lookup::AirLookupConfig;
-use ark_ff::PrimeField;
-use p3_bls12_377_fr::{Bls12_377Fr, FF_Bls12_377Fr};
+use air::preprocessed::FixedColumn;
+use p3_bls12_377_fr::Bls12_377Fr;
 use p3_field::{Field, FieldAlgebra};
+use p3_maybe_rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::collections::HashMap;
-use std::fs;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RawLookupTrace {
@@ -14,202 +19,199 @@ pub struct RawLookupTrace {
     pub name: String,
     pub a_filter: Vec<[u8; 32]>,
     pub b_filter: Vec<Vec<[u8; 32]>>,
+    /// Optional names of the source columns. Columns with the same name are committed once
+    /// per table; empty names are never shared.
+    #[serde(default)]
+    pub a_names: Vec<String>,
+    #[serde(default)]
+    pub b_names: Vec<Vec<String>>,
+    #[serde(default)]
+    pub a_filter_name: String,
+    #[serde(default)]
+    pub b_filter_names: Vec<String>,
+    /// Per B table, whether it is a constant table. Constant tables and their filters are
+    /// preprocessed columns committed in the verifying key instead of the main trace, so
+    /// the prover can't change them. Empty if there are none.
+    #[serde(default)]
+    pub b_fixed: Vec<bool>,
 }
 
 impl RawLookupTrace {
-    pub fn read_file(path: &str) -> Self {
-        let file_content = fs::read(path).unwrap();
-        let mut raw_trace: RawLookupTrace =
-            ciborium::from_reader(std::io::Cursor::new(file_content)).unwrap();
+    pub fn read_file(path: &str) -> Result<Self, TraceError> {
+        let mut raw_trace: RawLookupTrace = read_input(path, Self::validate, |t| t.name.clone())?;
+        raw_trace.fill_default_filters();
 
-        // We have to append filters (enabled) in case of filters have been passed empty
+        Ok(raw_trace)
+    }
+
+    /// Appends enabled filters in case of filters have been passed empty. Expects a valid
+    /// shape, see `validate`.
+    pub fn fill_default_filters(&mut self) {
         let mut one = [0u8; 32];
         one[31] = 1;
 
-        while raw_trace.a_filter.len() < raw_trace.a[0].len() {
-            raw_trace.a_filter.push(one);
+        while self.a_filter.len() < self.a[0].len() {
+            self.a_filter.push(one);
         }
 
-        while raw_trace.b_filter.len() < raw_trace.b.len() {
-            raw_trace.b_filter.push(Vec::new());
+        while self.b_filter.len() < self.b.len() {
+            self.b_filter.push(Vec::new());
         }
 
-        for (b_filter_ind, b_filter) in raw_trace.b_filter.iter_mut().enumerate() {
-            while b_filter.len() < raw_trace.b[b_filter_ind][0].len() {
+        for (b_filter_ind, b_filter) in self.b_filter.iter_mut().enumerate() {
+            while b_filter.len() < self.b[b_filter_ind][0].len() {
                 b_filter.push(one);
             }
         }
-
-        raw_trace
     }
 
-    pub(crate) fn get_trace(
-        &mut self,
-        challenges: Vec<Bls12_377Fr>,
-    ) -> (AirLookupConfig, Vec<Vec<Bls12_377Fr>>) {
-        assert_eq!(
-            challenges.len(),
-            2,
-            "Two challenges should be provided for the lookup trace"
-        );
-
-        // Unpack challenges
-        let (alpha, delta) = (challenges[0], challenges[1]);
-
-        // a columns, b columns, and corresponding filters
-        let (a, mut b, a_filter, b_filter) = self.get_columns();
-
-        // Resulting trace in one-dimensional array
-        let mut res: Vec<Vec<Bls12_377Fr>> = Vec::new();
-
-        res.append(&mut a.clone());
-
-        for b_element in b.iter() {
-            res.append(&mut b_element.clone());
-        }
-
-        res.push(a_filter.clone());
-        res.append(&mut b_filter.clone());
-
-        // Trace height
-        // !IMPORTANT: should be equal per all columns.
-        let sz = a[0].len();
-
-        // Amount of occurrence pre unique row in A
-        let mut occurrences: HashMap<Bls12_377Fr, usize> = HashMap::new();
+    pub fn is_b_fixed(&self, b_table: usize) -> bool {
+        self.b_fixed.get(b_table).copied().unwrap_or(false)
+    }
 
-        // Build occurrence mapping (should be done before trace generation)
-        // TODO: this is a partially repeated piece of code. Think how write it better.
-        for i in 0..sz {
-            // Skip is disabled by filter
-            if a_filter[i] == Bls12_377Fr::ZERO {
-                continue;
-            }
+    /// Raw main trace columns in the order of the argument layout: A columns, B columns
+    /// of every non-constant table, A filter and their B filters.
+    pub fn main_columns(&self) -> Vec<&[[u8; 32]]> {
+        let mut res: Vec<&[[u8; 32]]> = Vec::new();
 
-            let mut a_row_comb = Bls12_377Fr::ZERO;
-            for a_column in &a {
-                // Collect linear combination of the row
-                // `a_row_comb = a[i][j] * alpha^j` per all `j`
-                a_row_comb = a_row_comb * alpha + a_column[i];
+        res.extend(self.a.iter().map(Vec::as_slice));
+        for (b_table_ind, b_table) in self.b.iter().enumerate() {
+            if !self.is_b_fixed(b_table_ind) {
+                res.extend(b_table.iter().map(Vec::as_slice));
             }
-
-            // Update occurrences of the A row linear combination
-            if let Some(count) = occurrences.get(&a_row_comb) {
-                occurrences.insert(a_row_comb, *count + 1);
-            } else {
-                occurrences.insert(a_row_comb, 1);
+        }
+        res.push(&self.a_filter);
+        for (b_table_ind, b_filter) in self.b_filter.iter().enumerate() {
+            if !self.is_b_fixed(b_table_ind) {
+                res.push(b_filter);
             }
         }
 
-        let mut a_inverses_column = Vec::new();
-
-        let mut b_inverses_table: Vec<Vec<Bls12_377Fr>> =
-            (0..b.len()).map(|_| Vec::new()).collect();
-
-        let mut multiplicities_table: Vec<Vec<Bls12_377Fr>> =
-            (0..b.len()).map(|_| Vec::new()).collect();
-
-        let mut prefix_sum_column = Vec::new();
+        res
+    }
 
-        // Total sum of the log-derivative terms with corresponding multiplicities:
-        // one per each A row and -m[i] per each B row (m should be properly handled)
-        let mut log_derivative_sum = Bls12_377Fr::ZERO;
+    /// Preprocessed columns in the order of the argument layout: B columns of every
+    /// constant table, then their B filters.
+    pub fn fixed_columns(&self) -> Vec<FixedColumn> {
+        let mut res = Vec::new();
 
-        for i in 0..sz {
-            let mut a_row_comb = Bls12_377Fr::ZERO;
-            for a_column in &a {
-                // Iterate over all A columns and collect linear combination of the row
-                // `a_row_comb = a[i][j] * alpha^j` per all `j`
-                a_row_comb = a_row_comb * alpha + a_column[i];
+        for (b_table_ind, b_table) in self.b.iter().enumerate() {
+            if self.is_b_fixed(b_table_ind) {
+                res.extend(b_table.iter().cloned().map(FixedColumn::Values));
             }
-
-            let a_row_comb_inverse = (a_row_comb + delta).inverse();
-            a_inverses_column.push(a_row_comb_inverse);
-
-            // If the current A row is not disabled by filter
-            // (otherwise it is assumed to be multiplied on zero filter value)
-            if a_filter[i] != Bls12_377Fr::ZERO {
-                // Add A row log-derivative term
-                log_derivative_sum += a_row_comb_inverse
+        }
+        for (b_table_ind, b_filter) in self.b_filter.iter().enumerate() {
+            if self.is_b_fixed(b_table_ind) {
+                res.push(FixedColumn::Values(b_filter.clone()));
             }
+        }
 
-            for (b_table_ind, b_table) in b.iter().enumerate() {
-                let mut b_row_comb = Bls12_377Fr::ZERO;
-                for b_column in b_table {
-                    // Iterate over all B columns and collect linear combination of the row
-                    // `b_row_comb = b[i][j] * alpha^j` per all `j`
-                    b_row_comb = b_row_comb * alpha + b_column[i];
-                }
-
-                let b_row_comb_inverse = (b_row_comb + delta).inverse();
-                b_inverses_table[b_table_ind].push(b_row_comb_inverse);
-
-                let mut occurrence = Bls12_377Fr::ZERO;
-                if let Some(cnt) = occurrences.get(&b_row_comb) {
-                    if b_filter[b_table_ind][i] != Bls12_377Fr::ZERO {
-                        // If multiplicity is non-zero and B row is not disabled by filter, then:
-                        // - subtract from sum the corresponding log-derivative term
-                        // - remove multiplicity from occurrences
-                        occurrence = Bls12_377Fr::from_canonical_usize(*cnt);
-                        log_derivative_sum -= b_row_comb_inverse * occurrence;
-                        occurrences.remove(&b_row_comb);
-                    }
-                }
+        res
+    }
 
-                multiplicities_table[b_table_ind].push(occurrence);
-            }
+    /// Names of the raw main trace columns in the `main_columns` order, empty if unknown.
+    pub fn main_column_names(&self) -> Vec<String> {
+        let mut res: Vec<String> = Vec::new();
 
-            prefix_sum_column.push(log_derivative_sum);
+        res.extend((0..self.a.len()).map(|i| name_or_empty(&self.a_names, i)));
+        for (b_table_ind, b_table) in self.b.iter().enumerate() {
+            if self.is_b_fixed(b_table_ind) {
+                continue;
+            }
+            let b_names = self.b_names.get(b_table_ind).map_or(&[][..], Vec::as_slice);
+            res.extend((0..b_table.len()).map(|i| name_or_empty(b_names, i)));
         }
-
-        assert!(
-            prefix_sum_column.last().unwrap().is_zero(),
-            "failed to check constrain: check column should be 0 on the last row"
+        res.push(self.a_filter_name.clone());
+        res.extend(
+            (0..self.b.len())
+                .filter(|i| !self.is_b_fixed(*i))
+                .map(|i| name_or_empty(&self.b_filter_names, i)),
         );
 
-        res.push(a_inverses_column);
-        res.append(&mut b_inverses_table);
-        res.append(&mut multiplicities_table);
-        res.push(prefix_sum_column);
-
-        (self.get_air_lookup_config(a, b), res)
+        res
     }
 
-    fn get_air_lookup_config(
-        &self,
-        a: Vec<Vec<Bls12_377Fr>>,
-        b: Vec<Vec<Vec<Bls12_377Fr>>>,
-    ) -> AirLookupConfig {
-        let a_columns_ids = (0..a.len()).collect();
+    /// Returns the argument layout. Only depends on the amount of columns, so it doesn't
+    /// require decoding the trace. Main trace and preprocessed ids of the constant tables
+    /// are numbered separately, in the order of `main_columns` and `fixed_columns`.
+    pub fn get_air_config(&self) -> AirLookupConfig {
+        let a_columns_ids = (0..self.a.len()).collect();
+
+        // Next main trace and preprocessed ids
+        let mut next_ids = [self.a.len(), 0];
 
-        let mut b_columns_ids: Vec<Vec<usize>> = (0..b.len()).map(|_| Vec::new()).collect();
-        for i in 0..b.len() * b[0].len() {
-            b_columns_ids[i / b[0].len()].push(i + a.len());
+        let mut b_columns_ids: Vec<Vec<usize>> = Vec::new();
+        for (b_table_ind, b_table) in self.b.iter().enumerate() {
+            let next_id = &mut next_ids[self.is_b_fixed(b_table_ind) as usize];
+            b_columns_ids.push((*next_id..*next_id + b_table.len()).collect());
+            *next_id += b_table.len();
         }
 
-        let a_filter_id = *b_columns_ids.last().unwrap().last().unwrap() + 1;
+        let a_filter_id = next_ids[0];
+        next_ids[0] += 1;
 
-        let b_filter_id: Vec<usize> = (0..b.len()).map(|i| i + 1 + a_filter_id).collect();
+        let b_filter_id: Vec<usize> = (0..self.b.len())
+            .map(|b_table_ind| {
+                let next_id = &mut next_ids[self.is_b_fixed(b_table_ind) as usize];
+                *next_id += 1;
+                *next_id - 1
+            })
+            .collect();
+
+        let b_preprocessed = if self.b_fixed.contains(&true) {
+            (0..self.b.len()).map(|i| self.is_b_fixed(i)).collect()
+        } else {
+            vec![]
+        };
 
-        let a_inverses_id = b_filter_id.last().unwrap() + 1;
+        let a_inverses_id = 0;
 
-        let b_inverses_id: Vec<usize> = (0..b.len()).map(|i| i + 1 + a_inverses_id).collect();
+        let b_inverses_id: Vec<usize> = (0..self.b.len()).map(|i| i + 1 + a_inverses_id).collect();
 
-        let occurrences_id: Vec<usize> = (0..b.len())
+        let occurrences_id: Vec<usize> = (0..self.b.len())
             .map(|i| i + 1 + b_inverses_id.last().unwrap())
             .collect();
 
         let check_id = occurrences_id.last().unwrap() + 1;
 
+        let a_columns_names = (0..self.a.len())
+            .map(|i| column_label(&self.a_names, i, || format!("A[{}]", i)))
+            .collect();
+        let b_columns_names = self
+            .b
+            .iter()
+            .enumerate()
+            .map(|(b_table_ind, b_table)| {
+                let b_names = self.b_names.get(b_table_ind).map_or(&[][..], Vec::as_slice);
+                (0..b_table.len())
+                    .map(|i| column_label(b_names, i, || format!("B[{}][{}]", b_table_ind, i)))
+                    .collect()
+            })
+            .collect();
+        let a_filter_name = column_label(std::slice::from_ref(&self.a_filter_name), 0, || {
+            "A filter".to_string()
+        });
+        let b_filter_names = (0..self.b.len())
+            .map(|i| column_label(&self.b_filter_names, i, || format!("B[{}] filter", i)))
+            .collect();
+
         AirLookupConfig {
             a_columns_ids,
             b_columns_ids,
             a_filter_id,
             b_filter_id,
+            b_preprocessed,
+            unit_multiplicity: false,
+            tag: 0,
             a_inverses_id,
             b_inverses_id,
             occurrences_id,
             check_id,
+            shared_check: false,
+            a_columns_names,
+            b_columns_names,
+            a_filter_name,
+            b_filter_names,
         }
     }
 
@@ -227,76 +229,279 @@ impl RawLookupTrace {
 
         max_height
     }
+}
 
-    pub fn resize(&mut self, size: usize) {
-        for e in &mut self.a {
-            e.resize(size, [0u8; 32]);
-        }
+/// Generates auxiliary lookup columns (inverses, multiplicities and the log-derivative
+/// prefix sum) from the already committed main trace columns. Constant B tables are read
+/// from the row-major preprocessed columns `fixed`.
+///
+/// The prefix sum only holds the terms of this lookup, even if its running sum is shared.
+pub(crate) fn get_aux_trace(
+    name: &str,
+    cfg: &AirLookupConfig,
+    main: &[Bls12_377Fr],
+    width: usize,
+    fixed: &[Bls12_377Fr],
+    fixed_width: usize,
+    challenges: &[Bls12_377Fr],
+) -> Result<Vec<Vec<Bls12_377Fr>>, TraceError> {
+    let (columns, failure) =
+        get_unchecked_aux_trace(name, cfg, main, width, fixed, fixed_width, challenges)?;
+    failure.map_or(Ok(columns), Err)
+}
+
+/// Same as `get_aux_trace`, but also returns the columns of an unsatisfied lookup, along
+/// with the failure.
+pub(crate) fn get_unchecked_aux_trace(
+    name: &str,
+    cfg: &AirLookupConfig,
+    main: &[Bls12_377Fr],
+    width: usize,
+    fixed: &[Bls12_377Fr],
+    fixed_width: usize,
+    challenges: &[Bls12_377Fr],
+) -> Result<(Vec<Vec<Bls12_377Fr>>, Option<TraceError>), TraceError> {
+    let (alpha, delta) = unpack_challenges(name, challenges)?;
+
+    let a_filter = get_column(main, width, cfg.a_filter_id);
+
+    // Shifted linear combinations of the tagged rows, `comb = a[i][j] * alpha^j + delta` per
+    // all `j`
+    let a_combs = combine_tagged_rows(main, width, cfg.tag, &cfg.a_columns_ids, alpha, delta);
+    let b_combs: Vec<Vec<Bls12_377Fr>> = cfg
+        .b_columns_ids
+        .iter()
+        .enumerate()
+        .map(|(b_table_ind, b_columns_ids)| {
+            let (b_values, b_width) =
+                b_table_values(cfg, b_table_ind, (main, width), (fixed, fixed_width));
+            combine_tagged_rows(b_values, b_width, cfg.tag, b_columns_ids, alpha, delta)
+        })
+        .collect();
+
+    // Trace height
+    let sz = a_combs.len();
+
+    // Every enabled B row is counted once with unit multiplicities
+    let (mut multiplicities_table, unmatched_row) = if cfg.unit_multiplicity {
+        let b_filters = (0..cfg.b_filter_id.len())
+            .map(|b_table_ind| {
+                let (b_values, b_width) =
+                    b_table_values(cfg, b_table_ind, (main, width), (fixed, fixed_width));
+                get_column(b_values, b_width, cfg.b_filter_id[b_table_ind])
+            })
+            .collect();
+        (b_filters, None)
+    } else {
+        get_multiplicities(cfg, main, width, fixed, fixed_width)
+    };
+
+    // Log-derivative terms of all rows
+    let a_inverses_column = inverse_combs(name, &a_combs)?;
+
+    let mut b_inverses_table: Vec<Vec<Bls12_377Fr>> = b_combs
+        .iter()
+        .map(|b_combs| inverse_combs(name, b_combs))
+        .collect::<Result<_, _>>()?;
+
+    // Log-derivative terms with corresponding multiplicities:
+    // one per each A row and -m[i] per each B row
+    let log_derivative_terms: Vec<Bls12_377Fr> = (0..sz)
+        .into_par_iter()
+        .map(|i| {
+            // A rows disabled by filter are assumed to be multiplied on zero filter value
+            let mut term = if a_filter[i] != Bls12_377Fr::ZERO {
+                a_inverses_column[i]
+            } else {
+                Bls12_377Fr::ZERO
+            };
 
-        self.a_filter.resize(size, [0u8; 32]);
+            for (b_inverses, multiplicities) in b_inverses_table.iter().zip(&multiplicities_table) {
+                term -= b_inverses[i] * multiplicities[i];
+            }
 
-        for b_element in &mut self.b {
-            for e in b_element {
-                e.resize(size, [0u8; 32]);
+            term
+        })
+        .collect();
+
+    let prefix_sum_column = parallel_scan(&log_derivative_terms, |x, y| x + y);
+
+    // Check column should be 0 on the last row, which holds once every A row is matched
+    let failure = if cfg.unit_multiplicity {
+        (!prefix_sum_column.last().unwrap().is_zero()).then(|| TraceError::UnsatisfiedPermutation {
+            name: name.to_string(),
+            difference: PermutationDifference::from_lookup(cfg, main, width),
+        })
+    } else {
+        (unmatched_row.is_some() || !prefix_sum_column.last().unwrap().is_zero()).then(|| {
+            TraceError::UnsatisfiedLookup {
+                name: name.to_string(),
+                row: unmatched_row.unwrap_or(sz - 1),
             }
-        }
+        })
+    };
 
-        for b_filter in &mut self.b_filter {
-            b_filter.resize(size, [0u8; 32]);
-        }
+    let mut res: Vec<Vec<Bls12_377Fr>> = Vec::new();
+
+    res.push(a_inverses_column);
+    res.append(&mut b_inverses_table);
+    // Unit multiplicities are not committed
+    if !cfg.unit_multiplicity {
+        res.append(&mut multiplicities_table);
     }
+    res.push(prefix_sum_column);
 
-    pub fn get_columns(
-        &mut self,
-    ) -> (
-        Vec<Vec<Bls12_377Fr>>,
-        Vec<Vec<Vec<Bls12_377Fr>>>,
-        Vec<Bls12_377Fr>,
-        Vec<Vec<Bls12_377Fr>>,
-    ) {
-        let mut a: Vec<Vec<Bls12_377Fr>> = Vec::new();
-        let mut b: Vec<Vec<Vec<Bls12_377Fr>>> = Vec::new();
-
-        let mut a_filter: Vec<Bls12_377Fr> = Vec::new();
-        let mut b_filter: Vec<Vec<Bls12_377Fr>> = Vec::new();
-
-        for i in 0..self.a.len() {
-            a.push(Vec::new());
-            for j in 0..self.a[i].len() {
-                a[i].push(Bls12_377Fr::new(FF_Bls12_377Fr::from_be_bytes_mod_order(
-                    self.a[i][j].as_slice(),
-                )));
-            }
-        }
+    Ok((res, failure))
+}
 
-        for i in 0..self.a[0].len() {
-            a_filter.push(Bls12_377Fr::new(FF_Bls12_377Fr::from_be_bytes_mod_order(
-                self.a_filter[i].as_slice(),
-            )));
+/// Computes the multiplicity column of every B table from the exact tuples, so it doesn't
+/// depend on the challenges. The whole count of a tuple in enabled A rows goes to its first
+/// enabled B row, looking through the B tables in order and through the rows of a table
+/// from the top. Also returns the first enabled A row whose tuple is absent from B.
+fn get_multiplicities(
+    cfg: &AirLookupConfig,
+    main: &[Bls12_377Fr],
+    width: usize,
+    fixed: &[Bls12_377Fr],
+    fixed_width: usize,
+) -> (Vec<Vec<Bls12_377Fr>>, Option<usize>) {
+    // Amount of occurrences and the first row index per unique tuple in A
+    let mut occurrences: HashMap<Vec<Bls12_377Fr>, (usize, usize)> = HashMap::new();
+
+    for (i, row) in main.chunks(width).enumerate() {
+        // Skip if disabled by filter
+        if row[cfg.a_filter_id] == Bls12_377Fr::ZERO {
+            continue;
         }
 
-        for i in 0..self.b.len() {
-            b.push(Vec::new());
-
-            b_filter.push(Vec::new());
-            for j in 0..self.b[i].len() {
-                b[i].push(Vec::new());
-                for k in 0..self.b[i][j].len() {
-                    b[i][j].push(Bls12_377Fr::new(FF_Bls12_377Fr::from_be_bytes_mod_order(
-                        self.b[i][j][k].as_slice(),
-                    )));
-                }
-            }
-        }
+        let tuple = cfg.a_columns_ids.iter().map(|id| row[*id]).collect();
+        occurrences.entry(tuple).or_insert((0, i)).0 += 1;
+    }
+
+    let multiplicities_table = cfg
+        .b_columns_ids
+        .iter()
+        .zip(&cfg.b_filter_id)
+        .enumerate()
+        .map(|(b_table_ind, (b_columns_ids, b_filter_id))| {
+            let (b_values, b_width) =
+                b_table_values(cfg, b_table_ind, (main, width), (fixed, fixed_width));
+            b_values
+                .chunks(b_width)
+                .map(|row| {
+                    if row[*b_filter_id] == Bls12_377Fr::ZERO {
+                        return Bls12_377Fr::ZERO;
+                    }
+
+                    // The first enabled B row takes the whole count and removes the tuple
+                    let tuple: Vec<Bls12_377Fr> = b_columns_ids.iter().map(|id| row[*id]).collect();
+                    match occurrences.remove(&tuple) {
+                        Some((cnt, _)) => Bls12_377Fr::from_canonical_usize(cnt),
+                        None => Bls12_377Fr::ZERO,
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let unmatched_row = occurrences.values().map(|(_, row)| *row).min();
+
+    (multiplicities_table, unmatched_row)
+}
+
+/// Row-major values holding the columns of `B[b_table]` with their width: the
+/// preprocessed columns for constant tables, the main trace otherwise.
+pub(crate) fn b_table_values<'a>(
+    cfg: &AirLookupConfig,
+    b_table: usize,
+    main: (&'a [Bls12_377Fr], usize),
+    fixed: (&'a [Bls12_377Fr], usize),
+) -> (&'a [Bls12_377Fr], usize) {
+    if cfg.is_b_preprocessed(b_table) {
+        fixed
+    } else {
+        main
+    }
+}
+
+pub(crate) fn name_or_empty(names: &[String], i: usize) -> String {
+    names.get(i).cloned().unwrap_or_default()
+}
+
+/// Name of the column `i` for messages, `default` is its position for unnamed columns.
+pub(crate) fn column_label<D: FnOnce() -> String>(
+    names: &[String],
+    i: usize,
+    default: D,
+) -> String {
+    match names.get(i) {
+        Some(name) if !name.is_empty() => name.clone(),
+        _ => default(),
+    }
+}
 
-        for i in 0..self.b.len() {
-            for j in 0..self.b[i][0].len() {
-                b_filter[i].push(Bls12_377Fr::new(FF_Bls12_377Fr::from_be_bytes_mod_order(
-                    self.b_filter[i][j].as_slice(),
-                )));
+#[cfg(test)]
+mod tests {
+    use crate::error::TraceError;
+    use crate::lookup::RawLookupTrace;
+    use crate::testing::{column, lookup};
+    use crate::validation::ShapeViolation;
+    use crate::RawTrace;
+    use air::AirConfig;
+    use p3_bls12_377_fr::Bls12_377Fr;
+    use p3_field::FieldAlgebra;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn duplicate_b_rows_take_the_whole_count_once() {
+        // 5 is in two rows of B[0], the first of them disabled, and in B[1]
+        let mut lt = lookup("dup", &[5, 5, 5, 1], &[&[1, 5, 5, 0], &[5, 0, 0, 0]]);
+        lt.b_filter = vec![column(&[1, 0, 1, 1]), column(&[1, 1, 1, 1])];
+
+        let mut trace = RawTrace::new();
+        let cfgs = trace.push_traces(vec![], vec![lt], vec![]).unwrap();
+        let AirConfig::Lookup(cfg) = &cfgs[0] else {
+            panic!("lookup config expected");
+        };
+
+        let mut rng = thread_rng();
+        let challenges: Vec<Bls12_377Fr> = (0..2).map(|_| rng.sample(Standard)).collect();
+        let aux = trace.get_aux_trace(&cfgs, &challenges).unwrap();
+        let at = |row: usize, id: usize| aux.values[row * trace.aux_width + id];
+
+        let count = Bls12_377Fr::from_canonical_u64;
+        let b0: Vec<Bls12_377Fr> = (0..4).map(|row| at(row, cfg.occurrences_id[0])).collect();
+        let b1: Vec<Bls12_377Fr> = (0..4).map(|row| at(row, cfg.occurrences_id[1])).collect();
+        assert_eq!(b0, vec![count(1), count(0), count(3), count(0)]);
+        assert_eq!(b1, vec![count(0); 4]);
+
+        assert_eq!(at(3, cfg.check_id), Bls12_377Fr::ZERO);
+    }
+
+    #[test]
+    fn non_boolean_filters_are_rejected_on_load() {
+        let mut lt = lookup("non_boolean", &[1, 2], &[&[1, 2]]);
+        lt.a_filter = column(&[1, 2]);
+
+        let path = std::env::temp_dir().join(format!("non_boolean_{}.cbor", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        ciborium::into_writer(&lt, file).unwrap();
+        let result = RawLookupTrace::read_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        match result {
+            Err(TraceError::ShapeMismatch { name, violations }) => {
+                assert_eq!(name, "non_boolean");
+                assert_eq!(
+                    violations,
+                    vec![ShapeViolation::NonBooleanFilter {
+                        filter: "A".to_string(),
+                        row: 1,
+                    }]
+                );
             }
+            other => panic!("expected a shape mismatch, got {:?}", other.map(|t| t.name)),
         }
-
-        (a, b, a_filter, b_filter)
     }
 }
@@ -1,120 +1,156 @@
+use crate::diagnostics::PermutationDifference;
+use crate::error::TraceError;
+use crate::inverse::inverse_combs;
+use crate::lookup::{column_label, name_or_empty};
+use crate::scan::parallel_scan;
+use crate::{combine_rows, read_input, unpack_challenges};
+use air::air_lookup::AirLookupConfig;
 use air::air_permutation::AirPermutationConfig;
-use ark_ff::PrimeField;
-use p3_bls12_377_fr::{Bls12_377Fr, FF_Bls12_377Fr};
+use p3_bls12_377_fr::Bls12_377Fr;
 use p3_field::{Field, FieldAlgebra};
+use p3_maybe_rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
-use std::fs;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RawPermutationTrace {
     pub a: Vec<Vec<[u8; 32]>>,
     pub b: Vec<Vec<[u8; 32]>>,
     pub name: String,
+    /// Optional names of the source columns. Columns with the same name are committed once
+    /// per table; empty names are never shared.
+    #[serde(default)]
+    pub a_names: Vec<String>,
+    #[serde(default)]
+    pub b_names: Vec<String>,
+    /// Optional filters of the A and B rows, only enabled rows take part in the permutation.
+    /// If only one side is filtered, the other one is filled with ones.
+    #[serde(default)]
+    pub a_filter: Vec<[u8; 32]>,
+    #[serde(default)]
+    pub b_filter: Vec<[u8; 32]>,
+    #[serde(default)]
+    pub a_filter_name: String,
+    #[serde(default)]
+    pub b_filter_name: String,
 }
 
 impl RawPermutationTrace {
-    pub fn read_file(path: &str) -> Self {
-        let file_content = fs::read(path).unwrap();
-        let raw_trace: RawPermutationTrace =
-            ciborium::from_reader(std::io::Cursor::new(file_content)).unwrap();
-        raw_trace
-    }
-
-    pub fn get_trace(
-        &self,
-        challenges: Vec<Bls12_377Fr>,
-    ) -> (AirPermutationConfig, Vec<Vec<Bls12_377Fr>>) {
-        assert_eq!(
-            challenges.len(),
-            2,
-            "Two challenges should be provided for the lookup trace"
-        );
+    pub fn read_file(path: &str) -> Result<Self, TraceError> {
+        let mut raw_trace: RawPermutationTrace =
+            read_input(path, Self::validate, |t| t.name.clone())?;
+        raw_trace.fill_default_filters();
 
-        // Unpack challenges
-        let (alpha, delta) = (challenges[0], challenges[1]);
+        Ok(raw_trace)
+    }
 
-        // a columns, b columns
-        let (mut a, mut b) = self.get_columns();
+    pub fn is_filtered(&self) -> bool {
+        !self.a_filter.is_empty() || !self.b_filter.is_empty()
+    }
 
-        let sz = a[0].len();
+    /// Enables all rows of the unfiltered side if the other one is filtered, so that the
+    /// zero padding rows are disabled on both sides. Expects a valid shape, see `validate`.
+    pub fn fill_default_filters(&mut self) {
+        let mut one = [0u8; 32];
+        one[31] = 1;
 
-        let width = a.len();
+        if self.a_filter.is_empty() && !self.b_filter.is_empty() {
+            self.a_filter = vec![one; self.a[0].len()];
+        }
 
-        let mut res: Vec<Vec<Bls12_377Fr>> = Vec::new();
+        if self.b_filter.is_empty() && !self.a_filter.is_empty() {
+            self.b_filter = vec![one; self.b[0].len()];
+        }
+    }
 
-        res.append(&mut a.clone());
-        res.append(&mut b.clone());
+    /// Enables all rows of both sides if the permutation is unfiltered, so that the zero
+    /// padding rows are disabled. Lookups with unit multiplicities always have filters.
+    pub fn enable_all_rows(&mut self) {
+        let mut one = [0u8; 32];
+        one[31] = 1;
 
-        // Prefix multiplication of the permutation terms
-        let mut prev_check = Bls12_377Fr::ONE;
+        if !self.is_filtered() {
+            self.a_filter = vec![one; self.a[0].len()];
+            self.b_filter = vec![one; self.b[0].len()];
+        }
+    }
 
-        let mut b_inverse_column = Vec::new();
-        let mut perm_check_column = Vec::new();
+    /// Raw main trace columns in the order of the argument layout: A columns, B columns,
+    /// then A and B filters if filtered.
+    pub fn main_columns(&self) -> Vec<&[[u8; 32]]> {
+        let mut res: Vec<&[[u8; 32]]> = self.a.iter().chain(&self.b).map(Vec::as_slice).collect();
+        if self.is_filtered() {
+            res.push(&self.a_filter);
+            res.push(&self.b_filter);
+        }
+        res
+    }
 
-        for i in 0..sz {
-            let mut a_row_comb = Bls12_377Fr::ZERO;
-            for a_column in &a {
-                // Collect linear combination of the row
-                // `a_row_comb = a[i][j] * alpha^j` per all `j`
-                a_row_comb = a_row_comb * alpha + a_column[i];
-            }
+    /// Names of the raw main trace columns in the `main_columns` order, empty if unknown.
+    pub fn main_column_names(&self) -> Vec<String> {
+        let a_names = (0..self.a.len()).map(|i| name_or_empty(&self.a_names, i));
+        let b_names = (0..self.b.len()).map(|i| name_or_empty(&self.b_names, i));
+        let mut res: Vec<String> = a_names.chain(b_names).collect();
+        if self.is_filtered() {
+            res.push(self.a_filter_name.clone());
+            res.push(self.b_filter_name.clone());
+        }
+        res
+    }
 
-            let mut b_row_comb = Bls12_377Fr::ZERO;
-            for b_column in &b {
-                // Iterate over all B columns and collect linear combination of the row
-                // `b_row_comb = b[i][j] * alpha^j` per all `j`
-                b_row_comb = b_row_comb * alpha + b_column[i];
-            }
+    /// Returns the argument layout. Only depends on the amount of columns, so it doesn't
+    /// require decoding the trace.
+    pub fn get_air_config(&self) -> AirPermutationConfig {
+        let width = self.a.len();
+        let filtered = self.is_filtered();
 
-            let b_row_comb_inverse = (b_row_comb + delta).inverse();
-            b_inverse_column.push(b_row_comb_inverse);
-            prev_check = prev_check * (a_row_comb + delta) * b_row_comb_inverse;
-            perm_check_column.push(prev_check);
+        AirPermutationConfig {
+            a_columns_ids: (0..width).collect(),
+            b_columns_ids: (width..2 * width).collect(),
+            a_filter_id: filtered.then_some(2 * width),
+            b_filter_id: filtered.then_some(2 * width + 1),
+            b_inverse_id: 0,
+            check_id: 1,
+            a_columns_names: (0..width)
+                .map(|i| column_label(&self.a_names, i, || format!("A[{}]", i)))
+                .collect(),
+            b_columns_names: (0..width)
+                .map(|i| column_label(&self.b_names, i, || format!("B[{}]", i)))
+                .collect(),
+            a_filter_name: column_label(std::slice::from_ref(&self.a_filter_name), 0, || {
+                "A filter".to_string()
+            }),
+            b_filter_name: column_label(std::slice::from_ref(&self.b_filter_name), 0, || {
+                "B filter".to_string()
+            }),
         }
+    }
 
-        assert!(
-            perm_check_column.last().unwrap().is_one(),
-            "failed to check constrain: check column should be 1 on the last row"
-        );
-
-        res.push(b_inverse_column);
-        res.push(perm_check_column);
-
-        (
-            AirPermutationConfig {
-                a_columns_ids: (0..width).collect(),
-                b_columns_ids: (width..2 * width).collect(),
-                b_inverse_id: 2 * width,
-                check_id: 2 * width + 1,
-            },
-            res,
-        )
-    }
-
-    pub fn get_columns(&self) -> (Vec<Vec<Bls12_377Fr>>, Vec<Vec<Bls12_377Fr>>) {
-        let mut a: Vec<Vec<Bls12_377Fr>> = Vec::new();
-        let mut b: Vec<Vec<Bls12_377Fr>> = Vec::new();
-
-        for i in 0..self.a.len() {
-            a.push(Vec::new());
-            for j in 0..self.a[i].len() {
-                a[i].push(Bls12_377Fr::new(FF_Bls12_377Fr::from_be_bytes_mod_order(
-                    self.a[i][j].as_slice(),
-                )));
-            }
-        }
+    /// Returns the layout of the permutation proved as a lookup of A into a single B table
+    /// with unit multiplicities, so that it shares the log-derivative running sum with the
+    /// lookups. Expects enabled rows to be filtered, see `enable_all_rows`.
+    pub fn get_log_derivative_air_config(&self) -> AirLookupConfig {
+        let cfg = self.get_air_config();
+        let width = self.a.len();
 
-        for i in 0..self.b.len() {
-            b.push(Vec::new());
-            for j in 0..self.b[i].len() {
-                b[i].push(Bls12_377Fr::new(FF_Bls12_377Fr::from_be_bytes_mod_order(
-                    self.b[i][j].as_slice(),
-                )));
-            }
+        AirLookupConfig {
+            a_columns_ids: cfg.a_columns_ids,
+            b_columns_ids: vec![cfg.b_columns_ids],
+            a_filter_id: 2 * width,
+            b_filter_id: vec![2 * width + 1],
+            b_preprocessed: vec![],
+            unit_multiplicity: true,
+            tag: 0,
+            a_inverses_id: 0,
+            b_inverses_id: vec![1],
+            occurrences_id: vec![],
+            check_id: 2,
+            shared_check: false,
+            a_columns_names: cfg.a_columns_names,
+            b_columns_names: vec![cfg.b_columns_names],
+            a_filter_name: cfg.a_filter_name,
+            b_filter_names: vec![cfg.b_filter_name],
         }
-
-        (a, b)
     }
 
     pub fn get_max_height(&self) -> usize {
@@ -130,14 +166,196 @@ impl RawPermutationTrace {
 
         max_height
     }
+}
 
-    pub fn resize(&mut self, size: usize) {
-        for e in &mut self.a {
-            e.resize(size, [0u8; 32]);
-        }
+/// Generates auxiliary permutation columns (B inverses and the running product) from the
+/// already committed main trace columns. Rows disabled by a filter contribute a factor of one.
+pub(crate) fn get_aux_trace(
+    name: &str,
+    cfg: &AirPermutationConfig,
+    main: &[Bls12_377Fr],
+    width: usize,
+    challenges: &[Bls12_377Fr],
+) -> Result<Vec<Vec<Bls12_377Fr>>, TraceError> {
+    let (columns, failure) = get_unchecked_aux_trace(name, cfg, main, width, challenges)?;
+    failure.map_or(Ok(columns), Err)
+}
+
+/// Same as `get_aux_trace`, but also returns the columns of an unsatisfied permutation,
+/// along with the failure.
+pub(crate) fn get_unchecked_aux_trace(
+    name: &str,
+    cfg: &AirPermutationConfig,
+    main: &[Bls12_377Fr],
+    width: usize,
+    challenges: &[Bls12_377Fr],
+) -> Result<(Vec<Vec<Bls12_377Fr>>, Option<TraceError>), TraceError> {
+    let (alpha, delta) = unpack_challenges(name, challenges)?;
+
+    // Shifted linear combinations of the rows, `comb = a[i][j] * alpha^j + delta` per all `j`,
+    // replaced with one in disabled rows
+    let a_combs = filter_combs(
+        combine_rows(main, width, &cfg.a_columns_ids, alpha, delta),
+        main,
+        width,
+        cfg.a_filter_id,
+    );
+    let b_combs = filter_combs(
+        combine_rows(main, width, &cfg.b_columns_ids, alpha, delta),
+        main,
+        width,
+        cfg.b_filter_id,
+    );
+
+    let b_inverse_column = inverse_combs(name, &b_combs)?;
+
+    // Permutation terms, `a_comb / b_comb` per each row
+    let perm_terms: Vec<Bls12_377Fr> = a_combs
+        .par_iter()
+        .zip(b_inverse_column.par_iter())
+        .map(|(a_row_comb, b_row_comb_inverse)| *a_row_comb * *b_row_comb_inverse)
+        .collect();
+
+    // Prefix multiplication of the permutation terms
+    let perm_check_column = parallel_scan(&perm_terms, |x, y| x * y);
+
+    // Check column should be 1 on the last row
+    let failure =
+        (!perm_check_column.last().unwrap().is_one()).then(|| TraceError::UnsatisfiedPermutation {
+            name: name.to_string(),
+            difference: PermutationDifference::new(cfg, main, width),
+        });
 
-        for e in &mut self.b {
-            e.resize(size, [0u8; 32]);
+    Ok((vec![b_inverse_column, perm_check_column], failure))
+}
+
+/// Factors of the running product: `comb * filter + 1 - filter`, as constrained by the AIR.
+fn filter_combs(
+    mut combs: Vec<Bls12_377Fr>,
+    main: &[Bls12_377Fr],
+    width: usize,
+    filter_id: Option<usize>,
+) -> Vec<Bls12_377Fr> {
+    if let Some(filter_id) = filter_id {
+        combs
+            .par_iter_mut()
+            .zip(main.par_chunks(width))
+            .for_each(|(comb, row)| {
+                *comb = *comb * row[filter_id] + Bls12_377Fr::ONE - row[filter_id]
+            });
+    }
+    combs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TraceError;
+    use crate::permutation::RawPermutationTrace;
+    use crate::testing::{column, lookup, permutation};
+    use crate::RawTrace;
+    use air::AirConfig;
+    use p3_bls12_377_fr::Bls12_377Fr;
+    use p3_field::FieldAlgebra;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn check(pt: RawPermutationTrace) -> Result<(), TraceError> {
+        let mut trace = RawTrace::new();
+        let cfgs = trace.push_traces(vec![pt], vec![], vec![])?;
+
+        let mut rng = thread_rng();
+        let challenges: Vec<Bls12_377Fr> = (0..2).map(|_| rng.sample(Standard)).collect();
+        trace.get_aux_trace(&cfgs, &challenges).map(|_| ())
+    }
+
+    #[test]
+    fn disabled_rows_are_neutral() {
+        // Sides of different heights, 9 is disabled
+        let mut pt = permutation("filtered", &[1, 2, 9, 3], &[3, 1, 2]);
+        pt.a_filter = column(&[1, 1, 0, 1]);
+        pt.b_filter = column(&[1, 1, 1]);
+
+        assert!(check(pt).is_ok());
+    }
+
+    #[test]
+    fn only_enabled_rows_are_permuted() {
+        // Whole columns are a permutation, but the enabled rows are not
+        let mut pt = permutation("filtered", &[1, 2, 3, 4], &[4, 3, 2, 1]);
+        pt.a_filter = column(&[1, 1, 1, 0]);
+        pt.b_filter = column(&[1, 1, 1, 1]);
+
+        assert!(matches!(
+            check(pt),
+            Err(TraceError::UnsatisfiedPermutation { .. })
+        ));
+    }
+
+    #[test]
+    fn unfiltered_side_is_enabled() {
+        let mut pt = permutation("one_sided", &[1, 7, 2], &[2, 1]);
+        pt.a_filter = column(&[1, 0, 1]);
+
+        let mut filled = pt.clone();
+        filled.fill_default_filters();
+        assert_eq!(filled.b_filter, column(&[1, 1]));
+
+        let cfgs = RawTrace::new()
+            .push_traces(vec![pt.clone()], vec![], vec![])
+            .unwrap();
+        let AirConfig::Permutation(cfg) = &cfgs[0] else {
+            panic!("permutation config expected");
+        };
+        assert!(cfg.a_filter_id.is_some() && cfg.b_filter_id.is_some());
+
+        assert!(check(pt).is_ok());
+    }
+
+    /// Lays out a lookup and the permutation in the log-derivative mode and returns the
+    /// shared running sum, or the first failure.
+    fn check_log_derivative(pt: RawPermutationTrace) -> Result<Bls12_377Fr, TraceError> {
+        let mut trace = RawTrace {
+            log_derivative_permutations: true,
+            ..RawTrace::new()
+        };
+        let lt = lookup("lookup", &[3, 1, 3, 2], &[&[1, 2, 3, 4]]);
+        let cfgs = trace.push_traces(vec![pt], vec![lt], vec![])?;
+
+        let [AirConfig::Lookup(l), AirConfig::Lookup(p)] = cfgs.as_slice() else {
+            panic!("two lookup configs expected");
+        };
+        assert!(!l.unit_multiplicity && p.unit_multiplicity);
+        assert_eq!((l.tag, p.tag), (1, 2));
+        assert_eq!(l.check_id, p.check_id);
+        assert!(!l.shared_check && p.shared_check);
+
+        let mut rng = thread_rng();
+        let challenges: Vec<Bls12_377Fr> = (0..2).map(|_| rng.sample(Standard)).collect();
+        let aux = trace.get_aux_trace(&cfgs, &challenges)?;
+        Ok(aux.values[(trace.height - 1) * trace.aux_width + l.check_id])
+    }
+
+    #[test]
+    fn log_derivative_permutation_shares_the_running_sum() {
+        let pt = permutation("permutation", &[1, 2, 3, 4], &[4, 2, 1, 3]);
+
+        assert_eq!(check_log_derivative(pt).unwrap(), Bls12_377Fr::ZERO);
+    }
+
+    #[test]
+    fn unsatisfied_log_derivative_permutation_is_reported() {
+        // 3 is in the lookup B table, but not in the permutation B side
+        let pt = permutation("permutation", &[1, 2, 3, 4], &[4, 2, 1, 1]);
+
+        match check_log_derivative(pt) {
+            Err(TraceError::UnsatisfiedPermutation { name, difference }) => {
+                assert_eq!(name, "permutation");
+                assert_eq!(difference.a_only.len(), 1);
+                assert_eq!(difference.a_only[0].rows, vec![2]);
+                assert_eq!(difference.b_only.len(), 1);
+                assert_eq!(difference.b_only[0].rows, vec![3]);
+            }
+            other => panic!("expected an unsatisfied permutation, got {:?}", other),
         }
     }
 }
@@ -0,0 +1,351 @@
+use crate::error::TraceError;
+use crate::inverse::inverse_combs;
+use crate::lookup::column_label;
+use crate::scan::parallel_scan;
+use crate::{combine_rows, get_column, read_input, unpack_challenges};
+use air::air_range::{AirRangeConfig, RANGE_LIMB_BITS};
+use air::preprocessed::FixedColumn;
+use p3_bls12_377_fr::Bls12_377Fr;
+use p3_field::{Field, FieldAlgebra};
+use p3_maybe_rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::max;
+use std::collections::HashMap;
+
+/// Widest supported range, enough for 64-bit words.
+pub const MAX_RANGE_BITS: usize = 64;
+
+/// Range check of a single column: every enabled value must be below `2^bits`. The values
+/// are looked up into a preprocessed range table, which is committed in the verifying key
+/// and shared by all range checks of the same width within a table.
+///
+/// Values wider than `RANGE_LIMB_BITS` are decomposed into limbs of `RANGE_LIMB_BITS` and a
+/// narrower last limb, the limbs are committed as extra main columns and looked up instead.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawRangeTrace {
+    pub column: Vec<[u8; 32]>,
+    pub filter: Vec<[u8; 32]>,
+    pub bits: usize,
+    pub name: String,
+    /// Optional names of the source columns, see `RawLookupTrace`.
+    #[serde(default)]
+    pub column_name: String,
+    #[serde(default)]
+    pub filter_name: String,
+    /// Big-endian limbs of the column, computed by `fill_defaults`. Empty for a single limb.
+    #[serde(skip)]
+    pub limbs: Vec<Vec<[u8; 32]>>,
+}
+
+impl RawRangeTrace {
+    pub fn read_file(path: &str) -> Result<Self, TraceError> {
+        let mut raw_trace: RawRangeTrace = read_input(path, Self::validate, |t| t.name.clone())?;
+        raw_trace.fill_defaults();
+
+        Ok(raw_trace)
+    }
+
+    /// Enables all rows in case of the filter has been passed empty and splits the column
+    /// into limbs. Expects a valid shape, see `validate`.
+    pub fn fill_defaults(&mut self) {
+        let mut one = [0u8; 32];
+        one[31] = 1;
+
+        if self.filter.is_empty() {
+            self.filter = vec![one; self.column.len()];
+        }
+
+        let limbs = self.limb_bits().len();
+        if limbs > 1 && self.limbs.is_empty() {
+            self.limbs = (0..limbs)
+                .map(|limb| {
+                    self.column
+                        .iter()
+                        .map(|value| limb_value(value, limb, limb == limbs - 1))
+                        .collect()
+                })
+                .collect();
+        }
+    }
+
+    /// Width of every limb, a single limb of `bits` if the range is narrow enough.
+    pub fn limb_bits(&self) -> Vec<usize> {
+        let mut limb_bits = vec![RANGE_LIMB_BITS; self.bits.saturating_sub(1) / RANGE_LIMB_BITS];
+        limb_bits.push(self.bits - RANGE_LIMB_BITS * limb_bits.len());
+        limb_bits
+    }
+
+    /// Raw main trace columns in the order of the argument layout: the checked column, its
+    /// filter and the limbs, if any.
+    pub fn main_columns(&self) -> Vec<&[[u8; 32]]> {
+        let mut columns: Vec<&[[u8; 32]]> = vec![&self.column, &self.filter];
+        columns.extend(self.limbs.iter().map(Vec::as_slice));
+        columns
+    }
+
+    /// Names of the raw main trace columns in the `main_columns` order, empty if unknown.
+    /// Limbs of a named column are named after their bits, so that range checks of the same
+    /// column share them only if they are the same.
+    pub fn main_column_names(&self) -> Vec<String> {
+        let mut names = vec![self.column_name.clone(), self.filter_name.clone()];
+        let limb_bits = self.limb_bits();
+        if limb_bits.len() == 1 {
+            return names;
+        }
+
+        let mut low = 0;
+        for (limb, bits) in limb_bits.iter().enumerate() {
+            names.push(if self.column_name.is_empty() {
+                String::new()
+            } else if limb == limb_bits.len() - 1 {
+                format!("{}[{}..]", self.column_name, low)
+            } else {
+                format!("{}[{}..{}]", self.column_name, low, low + bits)
+            });
+            low += bits;
+        }
+        names
+    }
+
+    /// Preprocessed columns of the argument: the range table of every limb.
+    pub fn fixed_columns(&self) -> Vec<FixedColumn> {
+        self.limb_bits()
+            .into_iter()
+            .map(|bits| FixedColumn::Range { bits })
+            .collect()
+    }
+
+    /// Returns the argument layout. Only depends on the range width, so it doesn't require
+    /// decoding the trace.
+    pub fn get_air_config(&self) -> AirRangeConfig {
+        let limb_bits = self.limb_bits();
+        let limbs = limb_bits.len();
+        let names = self.main_column_names();
+
+        AirRangeConfig {
+            column_id: 0,
+            filter_id: 1,
+            limbs_id: if limbs > 1 {
+                (2..2 + limbs).collect()
+            } else {
+                vec![]
+            },
+            table_id: (0..limbs).collect(),
+            bits: self.bits,
+            inverses_id: (0..limbs).collect(),
+            table_inverses_id: (limbs..2 * limbs).collect(),
+            occurrences_id: (2 * limbs..3 * limbs).collect(),
+            check_id: 3 * limbs,
+            column_name: column_label(&names, 0, || "value".to_string()),
+            filter_name: column_label(&names, 1, || "filter".to_string()),
+            limbs_names: if limbs > 1 {
+                (0..limbs)
+                    .map(|limb| column_label(&names, 2 + limb, || format!("limb[{}]", limb)))
+                    .collect()
+            } else {
+                vec![]
+            },
+            table_names: limb_bits
+                .iter()
+                .map(|bits| format!("range_table[{}]", bits))
+                .collect(),
+            limb_bits,
+        }
+    }
+
+    /// Height of the checked column, but not less than the widest range table, which takes
+    /// at most `2^RANGE_LIMB_BITS` rows.
+    pub fn get_max_height(&self) -> usize {
+        max(self.column.len(), 1 << self.limb_bits()[0])
+    }
+}
+
+/// Big-endian `limb`-th little-endian limb of `RANGE_LIMB_BITS` of the big-endian `value`.
+/// The `last` limb takes all the remaining high bits, so out of range values fail its
+/// lookup instead of the decomposition.
+fn limb_value(value: &[u8; 32], limb: usize, last: bool) -> [u8; 32] {
+    let limb_bytes = RANGE_LIMB_BITS / 8;
+    let end = 32 - limb * limb_bytes;
+    let start = if last { 0 } else { end - limb_bytes };
+
+    let mut bytes = [0u8; 32];
+    bytes[32 - (end - start)..].copy_from_slice(&value[start..end]);
+    bytes
+}
+
+/// Generates auxiliary range check columns (inverses and multiplicities of the table values
+/// per limb, and the log-derivative prefix sum) from the already committed main trace
+/// columns and the row-major preprocessed columns `fixed`, which hold the range tables.
+pub(crate) fn get_aux_trace(
+    name: &str,
+    cfg: &AirRangeConfig,
+    main: &[Bls12_377Fr],
+    width: usize,
+    fixed: &[Bls12_377Fr],
+    fixed_width: usize,
+    challenges: &[Bls12_377Fr],
+) -> Result<Vec<Vec<Bls12_377Fr>>, TraceError> {
+    let (columns, failure) =
+        get_unchecked_aux_trace(name, cfg, main, width, fixed, fixed_width, challenges)?;
+    failure.map_or(Ok(columns), Err)
+}
+
+/// Same as `get_aux_trace`, but also returns the columns of an unsatisfied range check,
+/// along with the failure.
+pub(crate) fn get_unchecked_aux_trace(
+    name: &str,
+    cfg: &AirRangeConfig,
+    main: &[Bls12_377Fr],
+    width: usize,
+    fixed: &[Bls12_377Fr],
+    fixed_width: usize,
+    challenges: &[Bls12_377Fr],
+) -> Result<(Vec<Vec<Bls12_377Fr>>, Option<TraceError>), TraceError> {
+    let (alpha, delta) = unpack_challenges(name, challenges)?;
+
+    let filter = get_column(main, width, cfg.filter_id);
+
+    // Trace height
+    let sz = main.len() / width;
+
+    let mut inverses_columns = Vec::new();
+    let mut table_inverses_columns = Vec::new();
+    let mut occurrences_columns = Vec::new();
+    let mut unmatched_row: Option<usize> = None;
+
+    for (checked_id, table_id) in cfg.checked_columns_ids().into_iter().zip(&cfg.table_id) {
+        // Shifted values, `comb = value + delta`
+        let combs = combine_rows(main, width, &[checked_id], alpha, delta);
+        let table_combs = combine_rows(fixed, fixed_width, &[*table_id], alpha, delta);
+
+        // Amount of occurrences and the first row index per value in enabled rows
+        let mut occurrences: HashMap<Bls12_377Fr, (usize, usize)> = HashMap::new();
+        for (i, row) in main.chunks(width).enumerate() {
+            if row[cfg.filter_id] != Bls12_377Fr::ZERO {
+                occurrences.entry(row[checked_id]).or_insert((0, i)).0 += 1;
+            }
+        }
+
+        // The first table row of a value takes its whole count
+        let occurrences_column: Vec<Bls12_377Fr> = fixed
+            .chunks(fixed_width)
+            .map(|row| match occurrences.remove(&row[*table_id]) {
+                Some((cnt, _)) => Bls12_377Fr::from_canonical_usize(cnt),
+                None => Bls12_377Fr::ZERO,
+            })
+            .collect();
+
+        unmatched_row = unmatched_row
+            .into_iter()
+            .chain(occurrences.values().map(|(_, row)| *row))
+            .min();
+
+        inverses_columns.push(inverse_combs(name, &combs)?);
+        table_inverses_columns.push(inverse_combs(name, &table_combs)?);
+        occurrences_columns.push(occurrences_column);
+    }
+
+    // Log-derivative terms of all limbs, one per each enabled row and -m[i] per each table row
+    let log_derivative_terms: Vec<Bls12_377Fr> = (0..sz)
+        .into_par_iter()
+        .map(|i| {
+            (0..occurrences_columns.len())
+                .map(|limb| {
+                    filter[i] * inverses_columns[limb][i]
+                        - occurrences_columns[limb][i] * table_inverses_columns[limb][i]
+                })
+                .sum()
+        })
+        .collect();
+
+    let prefix_sum_column = parallel_scan(&log_derivative_terms, |x, y| x + y);
+
+    // Check column should be 0 on the last row, which holds once every limb is in range
+    let failure =
+        (unmatched_row.is_some() || !prefix_sum_column.last().unwrap().is_zero()).then(|| {
+            TraceError::UnsatisfiedLookup {
+                name: name.to_string(),
+                row: unmatched_row.unwrap_or(sz - 1),
+            }
+        });
+
+    let mut columns = inverses_columns;
+    columns.extend(table_inverses_columns);
+    columns.extend(occurrences_columns);
+    columns.push(prefix_sum_column);
+
+    Ok((columns, failure))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::TraceError;
+    use crate::range::RawRangeTrace;
+    use crate::testing::{column, range};
+    use crate::validation::ShapeViolation;
+    use crate::RawTrace;
+    use air::AirConfig;
+    use p3_bls12_377_fr::Bls12_377Fr;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn check(values: &[u64], bits: usize) -> Result<(), TraceError> {
+        let mut trace = RawTrace::new();
+        let cfgs = trace.push_traces(vec![], vec![], vec![range("range", values, bits)])?;
+
+        let mut rng = thread_rng();
+        let challenges: Vec<Bls12_377Fr> = (0..2).map(|_| rng.sample(Standard)).collect();
+        trace.get_aux_trace(&cfgs, &challenges).map(|_| ())
+    }
+
+    #[test]
+    fn wide_values_are_split_into_limbs() {
+        let mut rt = range("range", &[0x0012_3456_789a, 0xffff_ffff_ffff_ffff], 64);
+        rt.fill_defaults();
+
+        assert_eq!(rt.limb_bits(), vec![16; 4]);
+        assert_eq!(rt.limbs[0], column(&[0x789a, 0xffff]));
+        assert_eq!(rt.limbs[1], column(&[0x3456, 0xffff]));
+        assert_eq!(rt.limbs[2], column(&[0x0012, 0xffff]));
+        assert_eq!(rt.limbs[3], column(&[0, 0xffff]));
+
+        let cfgs = RawTrace::new()
+            .push_traces(vec![], vec![], vec![range("range", &[1], 20)])
+            .unwrap();
+        let AirConfig::Range(cfg) = &cfgs[0] else {
+            panic!("range config expected");
+        };
+        assert_eq!(cfg.limb_bits, vec![16, 4]);
+        assert_eq!(cfg.limbs_id.len(), 2);
+    }
+
+    #[test]
+    fn last_limb_bounds_the_value() {
+        assert!(check(&[0, 1 << 16, (1 << 20) - 1], 20).is_ok());
+        assert!(matches!(
+            check(&[0, 1 << 20], 20),
+            Err(TraceError::UnsatisfiedLookup { row: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn filter_violations_are_labelled_with_the_filter_name() {
+        let mut rt = range("range", &[1, 2, 3], 8);
+        rt.filter = column(&[1, 2]);
+
+        let labels = |rt: &RawRangeTrace| -> Vec<String> {
+            rt.validate()
+                .iter()
+                .map(|violation| match violation {
+                    ShapeViolation::NonBooleanFilter { filter, .. }
+                    | ShapeViolation::FilterLengthMismatch { filter, .. } => filter.clone(),
+                    other => panic!("unexpected violation {}", other),
+                })
+                .collect()
+        };
+        assert_eq!(labels(&rt), vec!["filter", "filter"]);
+
+        rt.filter_name = "range.ENABLED".to_string();
+        assert_eq!(labels(&rt), vec!["range.ENABLED", "range.ENABLED"]);
+    }
+}
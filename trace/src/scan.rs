@@ -0,0 +1,96 @@
+use p3_maybe_rayon::prelude::*;
+
+/// Amount of values scanned sequentially by a single task of [`parallel_scan`].
+const SCAN_CHUNK_SIZE: usize = 1 << 14;
+
+/// Inclusive scan: `res[i] = values[0] op ... op values[i]`.
+///
+/// Every chunk is scanned independently, then the chunk totals are accumulated
+/// sequentially and combined back into the chunks. `op` must be associative.
+pub(crate) fn parallel_scan<F, Op>(values: &[F], op: Op) -> Vec<F>
+where
+    F: Copy + Send + Sync,
+    Op: Fn(F, F) -> F + Sync,
+{
+    let mut res = values.to_vec();
+
+    res.par_chunks_mut(SCAN_CHUNK_SIZE).for_each(|chunk| {
+        for i in 1..chunk.len() {
+            chunk[i] = op(chunk[i - 1], chunk[i]);
+        }
+    });
+
+    // Total of all the preceding chunks, per chunk starting from the second one
+    let num_chunks = res.len().div_ceil(SCAN_CHUNK_SIZE);
+    let mut offsets: Vec<F> = Vec::new();
+    for chunk in res
+        .chunks(SCAN_CHUNK_SIZE)
+        .take(num_chunks.saturating_sub(1))
+    {
+        let last = chunk[chunk.len() - 1];
+        offsets.push(match offsets.last() {
+            Some(offset) => op(*offset, last),
+            None => last,
+        });
+    }
+
+    res.par_chunks_mut(SCAN_CHUNK_SIZE)
+        .skip(1)
+        .zip(offsets.par_iter())
+        .for_each(|(chunk, offset)| {
+            for value in chunk {
+                *value = op(*offset, *value);
+            }
+        });
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p3_bls12_377_fr::Bls12_377Fr;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn sequential_scan<Op: Fn(Bls12_377Fr, Bls12_377Fr) -> Bls12_377Fr>(
+        values: &[Bls12_377Fr],
+        op: Op,
+    ) -> Vec<Bls12_377Fr> {
+        let mut res: Vec<Bls12_377Fr> = Vec::with_capacity(values.len());
+        for value in values {
+            res.push(match res.last() {
+                Some(last) => op(*last, *value),
+                None => *value,
+            });
+        }
+        res
+    }
+
+    #[test]
+    fn parallel_scan_matches_sequential_across_chunks() {
+        let mut rng = thread_rng();
+
+        for len in [
+            1,
+            SCAN_CHUNK_SIZE,
+            SCAN_CHUNK_SIZE + 1,
+            3 * SCAN_CHUNK_SIZE + 7,
+        ] {
+            let values: Vec<Bls12_377Fr> = (0..len).map(|_| rng.sample(Standard)).collect();
+
+            assert_eq!(
+                parallel_scan(&values, |x, y| x + y),
+                sequential_scan(&values, |x, y| x + y),
+                "sum of {} values",
+                len
+            );
+            assert_eq!(
+                parallel_scan(&values, |x, y| x * y),
+                sequential_scan(&values, |x, y| x * y),
+                "product of {} values",
+                len
+            );
+        }
+    }
+}
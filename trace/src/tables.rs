@@ -0,0 +1,335 @@
+use crate::diagnostics::LookupDiagnostics;
+use crate::error::TraceError;
+use crate::lookup::RawLookupTrace;
+use crate::permutation::RawPermutationTrace;
+use crate::range::RawRangeTrace;
+use crate::{padded_height, RawTrace};
+use air::AirConfig;
+use p3_bls12_377_fr::Bls12_377Fr;
+use p3_matrix::dense::RowMajorMatrix;
+use std::cmp::{max, Reverse};
+use std::collections::{BTreeMap, HashMap};
+
+/// Arguments grouped into tables of the same padded height, so that small arguments are
+/// not padded to the height of the largest one. Every table is proved as its own matrix.
+///
+/// Arguments sharing a named column are placed into one table of the largest of their
+/// heights, so that the column is committed once and its copies are checked to be equal.
+pub struct RawTables {
+    /// Tables in decreasing order of height.
+    pub tables: Vec<RawTrace>,
+    /// Configs of the arguments of every table.
+    pub cfgs: Vec<Vec<AirConfig>>,
+}
+
+impl RawTables {
+    /// Groups the arguments into tables, see `RawTrace::log_derivative_permutations` for
+    /// the permutations mode.
+    pub fn new(
+        permutation_traces: Vec<RawPermutationTrace>,
+        lookup_traces: Vec<RawLookupTrace>,
+        range_traces: Vec<RawRangeTrace>,
+        min_height: usize,
+        log_derivative_permutations: bool,
+    ) -> Result<Self, TraceError> {
+        let heights = table_heights(
+            &lookup_traces,
+            &permutation_traces,
+            &range_traces,
+            min_height,
+        );
+        let groups = group_by_height(heights, lookup_traces, permutation_traces, range_traces);
+
+        let mut tables = Vec::new();
+        let mut cfgs = Vec::new();
+
+        for (lookup_traces, permutation_traces, range_traces) in groups {
+            let mut table = RawTrace {
+                log_derivative_permutations,
+                ..RawTrace::with_min_height(min_height)
+            };
+            cfgs.push(table.push_traces(permutation_traces, lookup_traces, range_traces)?);
+            tables.push(table);
+        }
+
+        Ok(RawTables { tables, cfgs })
+    }
+
+    /// Same tables as `new` with their layouts only, without validating or decoding the
+    /// inputs: the main and preprocessed values of the tables are left empty.
+    pub fn layouts(
+        permutation_traces: &[RawPermutationTrace],
+        lookup_traces: &[RawLookupTrace],
+        range_traces: &[RawRangeTrace],
+        min_height: usize,
+        log_derivative_permutations: bool,
+    ) -> Self {
+        let heights = table_heights(lookup_traces, permutation_traces, range_traces, min_height);
+        let groups = group_by_height(
+            heights,
+            lookup_traces.iter().collect(),
+            permutation_traces.iter().collect(),
+            range_traces.iter().collect(),
+        );
+
+        let mut tables = Vec::new();
+        let mut cfgs = Vec::new();
+
+        for (lookup_traces, permutation_traces, range_traces) in groups {
+            let mut table = RawTrace {
+                log_derivative_permutations,
+                ..RawTrace::with_min_height(min_height)
+            };
+            let layouts = table.lay_out_traces(&lookup_traces, &permutation_traces, &range_traces);
+            cfgs.push(layouts.into_iter().map(|(cfg, _)| cfg).collect());
+            tables.push(table);
+        }
+
+        RawTables { tables, cfgs }
+    }
+
+    /// Names of all arguments, table by table.
+    pub fn names(&self) -> Vec<String> {
+        self.tables
+            .iter()
+            .flat_map(|table| table.names.iter().cloned())
+            .collect()
+    }
+
+    /// Copies of the main traces of all tables, see `RawTrace::get_trace`.
+    pub fn get_traces(&self) -> Vec<RowMajorMatrix<Bls12_377Fr>> {
+        self.tables.iter().map(RawTrace::get_trace).collect()
+    }
+
+    /// Preprocessed columns of all tables, `None` for tables without any.
+    pub fn get_preprocessed_traces(&self) -> Vec<Option<RowMajorMatrix<Bls12_377Fr>>> {
+        self.tables
+            .iter()
+            .map(RawTrace::get_preprocessed_trace)
+            .collect()
+    }
+
+    /// Auxiliary traces of all tables for the shared challenges.
+    pub fn get_aux_traces(
+        &self,
+        challenges: &[Bls12_377Fr],
+    ) -> Result<Vec<RowMajorMatrix<Bls12_377Fr>>, TraceError> {
+        self.tables
+            .iter()
+            .zip(&self.cfgs)
+            .map(|(table, cfgs)| table.get_aux_trace(cfgs, challenges))
+            .collect()
+    }
+
+    /// Auxiliary traces of all tables, including the unsatisfied arguments, and the failures
+    /// of all arguments, see `RawTrace::get_unchecked_aux_trace`.
+    pub fn get_unchecked_aux_traces(
+        &self,
+        challenges: &[Bls12_377Fr],
+    ) -> Result<(Vec<RowMajorMatrix<Bls12_377Fr>>, Vec<TraceError>), TraceError> {
+        let mut aux_traces = Vec::new();
+        let mut failures = Vec::new();
+
+        for (table, cfgs) in self.tables.iter().zip(&self.cfgs) {
+            let (aux_trace, mut table_failures) =
+                table.get_unchecked_aux_trace(cfgs, challenges)?;
+            aux_traces.push(aux_trace);
+            failures.append(&mut table_failures);
+        }
+
+        Ok((aux_traces, failures))
+    }
+
+    /// Checks every argument of every table, see `RawTrace::check`.
+    pub fn check(&self, challenges: &[Bls12_377Fr]) -> Vec<TraceError> {
+        self.tables
+            .iter()
+            .zip(&self.cfgs)
+            .flat_map(|(table, cfgs)| table.check(cfgs, challenges))
+            .collect()
+    }
+
+    /// Lists the A rows missing from B for every lookup, see `RawTrace::diagnose_lookups`.
+    pub fn diagnose_lookups(&self, limit: usize) -> Vec<LookupDiagnostics> {
+        self.tables
+            .iter()
+            .zip(&self.cfgs)
+            .flat_map(|(table, cfgs)| table.diagnose_lookups(cfgs, limit))
+            .collect()
+    }
+}
+
+/// Lookups, permutations and range checks of one table.
+type Group<L, P, R> = (Vec<L>, Vec<P>, Vec<R>);
+
+/// Groups the arguments into tables, given the `table_heights` of the arguments, in
+/// decreasing order of height.
+fn group_by_height<L, P, R>(
+    heights: Vec<usize>,
+    lookup_traces: Vec<L>,
+    permutation_traces: Vec<P>,
+    range_traces: Vec<R>,
+) -> Vec<Group<L, P, R>> {
+    let mut heights = heights.into_iter();
+    let mut groups: BTreeMap<Reverse<usize>, Group<L, P, R>> = BTreeMap::new();
+
+    for lt in lookup_traces {
+        let height = heights.next().unwrap();
+        groups.entry(Reverse(height)).or_default().0.push(lt);
+    }
+
+    for pt in permutation_traces {
+        let height = heights.next().unwrap();
+        groups.entry(Reverse(height)).or_default().1.push(pt);
+    }
+
+    for rt in range_traces {
+        let height = heights.next().unwrap();
+        groups.entry(Reverse(height)).or_default().2.push(rt);
+    }
+
+    groups.into_values().collect()
+}
+
+/// Padded height of the table of every argument, lookups first and range checks last.
+/// Arguments connected by shared column names get the largest padded height among them.
+pub fn table_heights(
+    lookup_traces: &[RawLookupTrace],
+    permutation_traces: &[RawPermutationTrace],
+    range_traces: &[RawRangeTrace],
+    min_height: usize,
+) -> Vec<usize> {
+    let arguments: Vec<(usize, Vec<String>)> = lookup_traces
+        .iter()
+        .map(|lt| (lt.get_max_height(), lt.main_column_names()))
+        .chain(
+            permutation_traces
+                .iter()
+                .map(|pt| (pt.get_max_height(), pt.main_column_names())),
+        )
+        .chain(
+            range_traces
+                .iter()
+                .map(|rt| (rt.get_max_height(), rt.main_column_names())),
+        )
+        .collect();
+
+    // Union-find over the arguments, with the first argument of every name
+    let mut parent: Vec<usize> = (0..arguments.len()).collect();
+    let mut owners: HashMap<&str, usize> = HashMap::new();
+
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for (i, (_, names)) in arguments.iter().enumerate() {
+        for name in names.iter().filter(|name| !name.is_empty()) {
+            match owners.get(name.as_str()) {
+                Some(owner) => {
+                    let (a, b) = (root(&mut parent, i), root(&mut parent, *owner));
+                    parent[a] = b;
+                }
+                None => {
+                    owners.insert(name, i);
+                }
+            }
+        }
+    }
+
+    let mut heights = vec![0; arguments.len()];
+    for (i, (height, _)) in arguments.iter().enumerate() {
+        let r = root(&mut parent, i);
+        heights[r] = max(heights[r], padded_height(*height, min_height));
+    }
+
+    (0..arguments.len())
+        .map(|i| heights[root(&mut parent, i)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{lookup, permutation, range};
+
+    /// Lookups of 2 and 8 rows with the `x` A column, holding `large_x` in the larger one.
+    fn named_lookups(large_x: &[u64]) -> Vec<RawLookupTrace> {
+        let mut small = lookup("small", &[1, 2], &[&[1, 2]]);
+        small.a_names = vec!["x".to_string()];
+        let mut large = lookup("large", large_x, &[&[0, 1, 2, 3, 4, 5, 6, 7]]);
+        large.a_names = vec!["x".to_string()];
+        vec![small, large]
+    }
+
+    #[test]
+    fn shared_columns_are_placed_into_one_table() {
+        let tables = RawTables::new(
+            vec![],
+            named_lookups(&[1, 2, 0, 0, 0, 0, 0, 0]),
+            vec![],
+            1,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(tables.tables.len(), 1);
+        assert_eq!(tables.tables[0].height, 8);
+        assert_eq!(tables.tables[0].width, 7);
+    }
+
+    #[test]
+    fn shared_columns_of_different_heights_must_be_equal() {
+        let result = RawTables::new(
+            vec![],
+            named_lookups(&[1, 3, 0, 0, 0, 0, 0, 0]),
+            vec![],
+            1,
+            false,
+        );
+
+        assert!(matches!(
+            result,
+            Err(TraceError::SharedColumnMismatch { row: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn layouts_match_the_proved_tables() {
+        let lookups = named_lookups(&[1, 2, 0, 0, 0, 0, 0, 0]);
+        let permutations = vec![permutation("permutation", &[1, 2, 3], &[3, 2, 1])];
+        let ranges = vec![range("range", &[1, 1 << 20], 24)];
+
+        for log_derivative_permutations in [false, true] {
+            let layouts = RawTables::layouts(
+                &permutations,
+                &lookups,
+                &ranges,
+                4,
+                log_derivative_permutations,
+            );
+            let tables = RawTables::new(
+                permutations.clone(),
+                lookups.clone(),
+                ranges.clone(),
+                4,
+                log_derivative_permutations,
+            )
+            .unwrap();
+
+            assert_eq!(layouts.tables.len(), tables.tables.len());
+            for (layout, table) in layouts.tables.iter().zip(&tables.tables) {
+                assert_eq!(layout.height, table.height);
+                assert_eq!(layout.width, table.width);
+                assert_eq!(layout.aux_width, table.aux_width);
+                assert_eq!(layout.names, table.names);
+                assert_eq!(layout.column_names, table.column_names);
+                assert_eq!(layout.preprocessed, table.preprocessed);
+            }
+            assert_eq!(format!("{:?}", layouts.cfgs), format!("{:?}", tables.cfgs));
+        }
+    }
+}
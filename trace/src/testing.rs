@@ -0,0 +1,61 @@
+//! Builders of small raw inputs for the unit tests, also available to other crates with
+//! the `testing` feature.
+
+use crate::lookup::RawLookupTrace;
+use crate::permutation::RawPermutationTrace;
+use crate::range::RawRangeTrace;
+
+/// Big-endian encoding of `value`, as in the raw trace formats.
+pub fn value(value: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+pub fn column(values: &[u64]) -> Vec<[u8; 32]> {
+    values.iter().map(|v| value(*v)).collect()
+}
+
+/// Unfiltered lookup of the single column `a` into the single column tables `b`.
+pub fn lookup(name: &str, a: &[u64], b: &[&[u64]]) -> RawLookupTrace {
+    RawLookupTrace {
+        a: vec![column(a)],
+        b: b.iter().map(|b_table| vec![column(b_table)]).collect(),
+        name: name.to_string(),
+        a_filter: vec![],
+        b_filter: vec![],
+        a_names: vec![],
+        b_names: vec![],
+        a_filter_name: String::new(),
+        b_filter_names: vec![],
+        b_fixed: vec![],
+    }
+}
+
+/// Unfiltered permutation of the single columns `a` and `b`.
+pub fn permutation(name: &str, a: &[u64], b: &[u64]) -> RawPermutationTrace {
+    RawPermutationTrace {
+        a: vec![column(a)],
+        b: vec![column(b)],
+        name: name.to_string(),
+        a_names: vec![],
+        b_names: vec![],
+        a_filter: vec![],
+        b_filter: vec![],
+        a_filter_name: String::new(),
+        b_filter_name: String::new(),
+    }
+}
+
+/// Unfiltered range check of the column `values`.
+pub fn range(name: &str, values: &[u64], bits: usize) -> RawRangeTrace {
+    RawRangeTrace {
+        column: column(values),
+        filter: vec![],
+        bits,
+        name: name.to_string(),
+        column_name: String::new(),
+        filter_name: String::new(),
+        limbs: vec![],
+    }
+}
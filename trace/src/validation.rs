@@ -0,0 +1,349 @@
+use crate::lookup::RawLookupTrace;
+use crate::permutation::RawPermutationTrace;
+use crate::range::{RawRangeTrace, MAX_RANGE_BITS};
+use std::fmt::{Display, Formatter};
+
+/// Single shape or filter value problem of a raw lookup or permutation input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShapeViolation {
+    /// `A`, `B` or a `B[i]` table has no columns.
+    NoColumns { columns: String },
+    /// Column `column` of a column set has another height than the first column of the set.
+    HeightMismatch {
+        column: String,
+        height: usize,
+        expected: usize,
+    },
+    /// Tuples of `B[b_table]` have another arity than the A tuples.
+    TupleWidthMismatch {
+        b_table: usize,
+        a_width: usize,
+        b_width: usize,
+    },
+    /// Non-empty filter has another length than the columns it selects.
+    FilterLengthMismatch {
+        filter: String,
+        length: usize,
+        expected: usize,
+    },
+    /// Amount of B filters is neither zero nor the amount of B tables.
+    FilterCountMismatch { found: usize, expected: usize },
+    /// Amount of constant B table flags is neither zero nor the amount of B tables.
+    FixedCountMismatch { found: usize, expected: usize },
+    /// Filter value at `row` is neither zero nor one.
+    NonBooleanFilter { filter: String, row: usize },
+    /// Range is wider than `MAX_RANGE_BITS`.
+    RangeTooWide { bits: usize, max: usize },
+    /// Non-empty list of column names has another length than the columns it names.
+    NameCountMismatch {
+        columns: String,
+        found: usize,
+        expected: usize,
+    },
+    /// Sides of an unfiltered permutation have different heights, so their zero padding rows
+    /// can't match each other. Filtered permutations disable the padding rows instead.
+    UnfilteredHeightMismatch { a_height: usize, b_height: usize },
+}
+
+impl ShapeViolation {
+    /// Whether the violation is a height mismatch, which prevents padding the argument with
+    /// neutral zero rows.
+    pub fn blocks_padding(&self) -> bool {
+        matches!(
+            self,
+            ShapeViolation::HeightMismatch { .. }
+                | ShapeViolation::FilterLengthMismatch { .. }
+                | ShapeViolation::UnfilteredHeightMismatch { .. }
+        )
+    }
+}
+
+impl Display for ShapeViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShapeViolation::NoColumns { columns } => write!(f, "{} has no columns", columns),
+            ShapeViolation::HeightMismatch {
+                column,
+                height,
+                expected,
+            } => write!(
+                f,
+                "column {} has height {}, expected {}",
+                column, height, expected
+            ),
+            ShapeViolation::TupleWidthMismatch {
+                b_table,
+                a_width,
+                b_width,
+            } => write!(
+                f,
+                "B[{}] has {} columns while A has {}",
+                b_table, b_width, a_width
+            ),
+            ShapeViolation::FilterLengthMismatch {
+                filter,
+                length,
+                expected,
+            } => write!(
+                f,
+                "filter {} has length {}, expected {}",
+                filter, length, expected
+            ),
+            ShapeViolation::FilterCountMismatch { found, expected } => {
+                write!(f, "{} B filters provided for {} B tables", found, expected)
+            }
+            ShapeViolation::FixedCountMismatch { found, expected } => write!(
+                f,
+                "{} constant table flags provided for {} B tables",
+                found, expected
+            ),
+            ShapeViolation::NonBooleanFilter { filter, row } => {
+                write!(f, "filter {} is neither 0 nor 1 at row {}", filter, row)
+            }
+            ShapeViolation::RangeTooWide { bits, max } => write!(
+                f,
+                "range of {} bits is wider than the maximum of {} bits",
+                bits, max
+            ),
+            ShapeViolation::NameCountMismatch {
+                columns,
+                found,
+                expected,
+            } => write!(
+                f,
+                "{} names provided for {} columns of {}",
+                found, expected, columns
+            ),
+            ShapeViolation::UnfilteredHeightMismatch { a_height, b_height } => write!(
+                f,
+                "A has height {} and B has height {}, which requires filters",
+                a_height, b_height
+            ),
+        }
+    }
+}
+
+/// Checks that all columns of a set have the height of the first one and returns it.
+fn check_heights(
+    set_name: &str,
+    columns: &[Vec<[u8; 32]>],
+    violations: &mut Vec<ShapeViolation>,
+) -> Option<usize> {
+    if columns.is_empty() {
+        violations.push(ShapeViolation::NoColumns {
+            columns: set_name.to_string(),
+        });
+        return None;
+    }
+
+    let expected = columns[0].len();
+    for (i, column) in columns.iter().enumerate() {
+        if column.len() != expected {
+            violations.push(ShapeViolation::HeightMismatch {
+                column: format!("{}[{}]", set_name, i),
+                height: column.len(),
+                expected,
+            });
+        }
+    }
+
+    Some(expected)
+}
+
+/// Checks that every value of a filter is a big-endian zero or one, only the first
+/// offending row is reported.
+fn check_boolean(filter_name: &str, filter: &[[u8; 32]], violations: &mut Vec<ShapeViolation>) {
+    let is_boolean = |value: &[u8; 32]| value[..31].iter().all(|b| *b == 0) && value[31] <= 1;
+
+    if let Some(row) = filter.iter().position(|value| !is_boolean(value)) {
+        violations.push(ShapeViolation::NonBooleanFilter {
+            filter: filter_name.to_string(),
+            row,
+        });
+    }
+}
+
+/// Checks that the names of a column set, if any, match its columns one to one.
+fn check_names<T>(
+    set_name: &str,
+    names: &[T],
+    expected: usize,
+    violations: &mut Vec<ShapeViolation>,
+) {
+    if !names.is_empty() && names.len() != expected {
+        violations.push(ShapeViolation::NameCountMismatch {
+            columns: set_name.to_string(),
+            found: names.len(),
+            expected,
+        });
+    }
+}
+
+impl RawLookupTrace {
+    /// Returns every shape violation of the decoded input. Empty filters are allowed and
+    /// treated as all-enabled, the others must only hold zeros and ones.
+    pub fn validate(&self) -> Vec<ShapeViolation> {
+        let mut violations = Vec::new();
+
+        let a_height = check_heights("A", &self.a, &mut violations);
+
+        if self.b.is_empty() {
+            violations.push(ShapeViolation::NoColumns {
+                columns: "B".to_string(),
+            });
+        }
+
+        check_boolean("A", &self.a_filter, &mut violations);
+
+        if let Some(a_height) = a_height {
+            if !self.a_filter.is_empty() && self.a_filter.len() != a_height {
+                violations.push(ShapeViolation::FilterLengthMismatch {
+                    filter: "A".to_string(),
+                    length: self.a_filter.len(),
+                    expected: a_height,
+                });
+            }
+        }
+
+        if !self.b_filter.is_empty() && self.b_filter.len() != self.b.len() {
+            violations.push(ShapeViolation::FilterCountMismatch {
+                found: self.b_filter.len(),
+                expected: self.b.len(),
+            });
+        }
+
+        if !self.b_fixed.is_empty() && self.b_fixed.len() != self.b.len() {
+            violations.push(ShapeViolation::FixedCountMismatch {
+                found: self.b_fixed.len(),
+                expected: self.b.len(),
+            });
+        }
+
+        check_names("A", &self.a_names, self.a.len(), &mut violations);
+        check_names("B", &self.b_names, self.b.len(), &mut violations);
+        check_names(
+            "B filters",
+            &self.b_filter_names,
+            self.b.len(),
+            &mut violations,
+        );
+
+        for (b_table_ind, b_table) in self.b.iter().enumerate() {
+            let b_height = check_heights(&format!("B[{}]", b_table_ind), b_table, &mut violations);
+
+            if !self.a.is_empty() && !b_table.is_empty() && b_table.len() != self.a.len() {
+                violations.push(ShapeViolation::TupleWidthMismatch {
+                    b_table: b_table_ind,
+                    a_width: self.a.len(),
+                    b_width: b_table.len(),
+                });
+            }
+
+            if let Some(b_names) = self.b_names.get(b_table_ind) {
+                check_names(
+                    &format!("B[{}]", b_table_ind),
+                    b_names,
+                    b_table.len(),
+                    &mut violations,
+                );
+            }
+
+            if let Some(b_filter) = self.b_filter.get(b_table_ind) {
+                check_boolean(&format!("B[{}]", b_table_ind), b_filter, &mut violations);
+            }
+
+            if let (Some(b_height), Some(b_filter)) = (b_height, self.b_filter.get(b_table_ind)) {
+                if !b_filter.is_empty() && b_filter.len() != b_height {
+                    violations.push(ShapeViolation::FilterLengthMismatch {
+                        filter: format!("B[{}]", b_table_ind),
+                        length: b_filter.len(),
+                        expected: b_height,
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+impl RawPermutationTrace {
+    /// Returns every shape violation of the decoded input. Empty filters are allowed, the
+    /// others must only hold zeros and ones.
+    pub fn validate(&self) -> Vec<ShapeViolation> {
+        let mut violations = Vec::new();
+
+        let a_height = check_heights("A", &self.a, &mut violations);
+        let b_height = check_heights("B", &self.b, &mut violations);
+
+        if !self.a.is_empty() && !self.b.is_empty() && self.a.len() != self.b.len() {
+            violations.push(ShapeViolation::TupleWidthMismatch {
+                b_table: 0,
+                a_width: self.a.len(),
+                b_width: self.b.len(),
+            });
+        }
+
+        check_names("A", &self.a_names, self.a.len(), &mut violations);
+        check_names("B", &self.b_names, self.b.len(), &mut violations);
+
+        let filters = [
+            ("A", &self.a_filter, a_height),
+            ("B", &self.b_filter, b_height),
+        ];
+        for (filter_name, filter, height) in filters {
+            check_boolean(filter_name, filter, &mut violations);
+
+            if let Some(height) = height {
+                if !filter.is_empty() && filter.len() != height {
+                    violations.push(ShapeViolation::FilterLengthMismatch {
+                        filter: filter_name.to_string(),
+                        length: filter.len(),
+                        expected: height,
+                    });
+                }
+            }
+        }
+
+        // Filters disable the padding rows of the shorter side, otherwise they must match
+        if let (Some(a_height), Some(b_height)) = (a_height, b_height) {
+            if a_height != b_height && !self.is_filtered() {
+                violations.push(ShapeViolation::UnfilteredHeightMismatch { a_height, b_height });
+            }
+        }
+
+        violations
+    }
+}
+
+impl RawRangeTrace {
+    /// Returns every shape violation of the decoded input. An empty filter is allowed and
+    /// treated as all-enabled.
+    pub fn validate(&self) -> Vec<ShapeViolation> {
+        let mut violations = Vec::new();
+
+        if self.bits > MAX_RANGE_BITS {
+            violations.push(ShapeViolation::RangeTooWide {
+                bits: self.bits,
+                max: MAX_RANGE_BITS,
+            });
+        }
+
+        let filter_name = if self.filter_name.is_empty() {
+            "filter"
+        } else {
+            self.filter_name.as_str()
+        };
+        check_boolean(filter_name, &self.filter, &mut violations);
+
+        if !self.filter.is_empty() && self.filter.len() != self.column.len() {
+            violations.push(ShapeViolation::FilterLengthMismatch {
+                filter: filter_name.to_string(),
+                length: self.filter.len(),
+                expected: self.column.len(),
+            });
+        }
+
+        violations
+    }
+}